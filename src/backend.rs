@@ -0,0 +1,73 @@
+use composite::{Blend, Composite};
+use framebuffer::Rgba;
+use util::Point;
+
+pub trait Surface {
+    fn set_color(&mut self, color: Rgba);
+    fn plot(&mut self, p: Point);
+    fn plot_span(&mut self, x0: i32, x1: i32, y: i32) { //Inclusive on both ends
+        for x in x0..x1 + 1 {
+            self.plot((x, y));
+        }
+    }
+    fn plot_coverage(&mut self, p: Point, coverage: f32) { //Coverage in 0..1; surfaces that can't blend round it
+        if coverage >= 0.5 {
+            self.plot(p);
+        }
+    }
+    fn composite(&mut self, p: Point, color: Rgba, _op: Composite, _blend: Blend) { //Surfaces that can't read their pixels back treat everything as normal source-over
+        self.set_color((color.0, color.1, color.2, 255));
+        self.plot_coverage(p, color.3 as f32 / 255.);
+    }
+    fn clear(&mut self, color: Rgba);
+    fn present(&mut self) {}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Clear(Rgba),
+    Plot(Point, Rgba),
+    PlotSpan(i32, i32, i32, Rgba), //First and last x, inclusive, then the row
+    PlotCoverage(Point, Rgba, f32),
+    Present,
+}
+
+#[derive(Default)]
+pub struct Recorder { //Keeps every call made on it, for tests and debugging
+    color: Rgba,
+    pub ops: Vec<Op>,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+    pub fn plotted(&self) -> Vec<(Point, Rgba)> { //Pixels set outright, with spans taken apart
+        self.ops.iter().flat_map(|op| match *op {
+            Op::Plot(p, c) => vec![(p, c)],
+            Op::PlotSpan(x0, x1, y, c) => (x0..x1 + 1).map(|x| ((x, y), c)).collect(),
+            _ => Vec::new(),
+        }).collect()
+    }
+}
+
+impl Surface for Recorder {
+    fn set_color(&mut self, color: Rgba) {
+        self.color = color;
+    }
+    fn plot(&mut self, p: Point) {
+        self.ops.push(Op::Plot(p, self.color));
+    }
+    fn plot_span(&mut self, x0: i32, x1: i32, y: i32) {
+        self.ops.push(Op::PlotSpan(x0, x1, y, self.color));
+    }
+    fn plot_coverage(&mut self, p: Point, coverage: f32) {
+        self.ops.push(Op::PlotCoverage(p, self.color, coverage));
+    }
+    fn clear(&mut self, color: Rgba) {
+        self.ops.push(Op::Clear(color));
+    }
+    fn present(&mut self) {
+        self.ops.push(Op::Present);
+    }
+}
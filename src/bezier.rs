@@ -0,0 +1,154 @@
+use std::f32::consts::FRAC_PI_2;
+
+use transform::{Angle, Transform};
+use util::Point;
+
+type Vec2 = (f32, f32);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum Curve { //Continues from the end of the previous curve
+    Quad(Point, Point), //Control, end
+    Cubic(Point, Point, Point), //Control, control, end
+}
+
+pub fn default_tolerance() -> f32 {
+    0.25
+}
+
+const MAX_DEPTH: u32 = 16;
+
+fn mid(a: Vec2, b: Vec2) -> Vec2 {
+    ((a.0 + b.0) / 2., (a.1 + b.1) / 2.)
+}
+
+fn distance_to_chord(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len = dx.hypot(dy);
+    if len == 0. {
+        return (p.0 - a.0).hypot(p.1 - a.1);
+    }
+    ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / len
+}
+
+pub fn flatten_quad(p0: Vec2, p1: Vec2, p2: Vec2, tolerance: f32) -> Vec<Vec2> { //Points after p0, ending at p2
+    let mut points = Vec::new();
+    subdivide_quad(p0, p1, p2, tolerance.max(0.01), 0, &mut points);
+    points
+}
+
+fn subdivide_quad(p0: Vec2, p1: Vec2, p2: Vec2, tolerance: f32, depth: u32, points: &mut Vec<Vec2>) {
+    //The curve strays at most half as far from the chord as its control point
+    if depth >= MAX_DEPTH || distance_to_chord(p1, p0, p2) / 2. <= tolerance {
+        points.push(p2);
+        return;
+    }
+    let a = mid(p0, p1);
+    let b = mid(p1, p2);
+    let m = mid(a, b);
+    subdivide_quad(p0, a, m, tolerance, depth + 1, points);
+    subdivide_quad(m, b, p2, tolerance, depth + 1, points);
+}
+
+pub fn flatten_cubic(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, tolerance: f32) -> Vec<Vec2> { //Points after p0, ending at p3
+    let mut points = Vec::new();
+    subdivide_cubic(p0, p1, p2, p3, tolerance.max(0.01), 0, &mut points);
+    points
+}
+
+fn subdivide_cubic(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, tolerance: f32, depth: u32, points: &mut Vec<Vec2>) {
+    //The curve stays within 3/4 of the control points' distance from the chord
+    let d = distance_to_chord(p1, p0, p3).max(distance_to_chord(p2, p0, p3));
+    if depth >= MAX_DEPTH || d * 0.75 <= tolerance {
+        points.push(p3);
+        return;
+    }
+    let a = mid(p0, p1);
+    let b = mid(p1, p2);
+    let c = mid(p2, p3);
+    let ab = mid(a, b);
+    let bc = mid(b, c);
+    let m = mid(ab, bc);
+    subdivide_cubic(p0, a, ab, m, tolerance, depth + 1, points);
+    subdivide_cubic(m, bc, c, p3, tolerance, depth + 1, points);
+}
+
+pub fn arc_cubics(center: Vec2, radii: Vec2, rotation: Angle, start: f32, sweep: f32) -> Vec<[Vec2; 4]> { //Elliptical arc as cubics of at most a quarter turn; angles in radians
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let map = |p: Vec2| {
+        let (x, y) = (p.0 * radii.0, p.1 * radii.1);
+        (center.0 + x * cos - y * sin, center.1 + x * sin + y * cos)
+    };
+    let n = ((sweep.abs() / FRAC_PI_2).ceil() as usize).max(1);
+    let step = sweep / n as f32;
+    let k = 4. / 3. * (step / 4.).tan(); //Control arm length that matches the unit circle at the middle of the segment
+    (0..n).map(|i| {
+        let (s0, c0) = (start + step * i as f32).sin_cos();
+        let (s1, c1) = (start + step * (i + 1) as f32).sin_cos();
+        [map((c0, s0)), map((c0 - k * s0, s0 + k * c0)), map((c1 + k * s1, s1 - k * c1)), map((c1, s1))]
+    }).collect()
+}
+
+pub fn flatten(start: Point, curves: &[Curve], tolerance: f32, t: &Transform) -> Vec<Vec2> { //Screen space polyline through the curves
+    //Béziers are affine invariant, so transforming the control points first keeps the tolerance in screen pixels
+    let apply = |p: Point| t.apply((p.0 as f32, p.1 as f32));
+    let mut from = apply(start);
+    let mut points = vec![from];
+    for curve in curves {
+        match *curve {
+            Curve::Quad(c, end) => points.extend(flatten_quad(from, apply(c), apply(end), tolerance)),
+            Curve::Cubic(c0, c1, end) => points.extend(flatten_cubic(from, apply(c0), apply(c1), apply(end), tolerance)),
+        }
+        from = points[points.len() - 1];
+    }
+    points
+}
+
+pub fn control_points(start: Point, curves: &[Curve]) -> Vec<Point> { //The curve stays within their hull
+    let mut points = vec![start];
+    for curve in curves {
+        match *curve {
+            Curve::Quad(c, end) => points.extend_from_slice(&[c, end]),
+            Curve::Cubic(c0, c1, end) => points.extend_from_slice(&[c0, c1, end]),
+        }
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn max_error<F: Fn(f32) -> Vec2>(points: &[Vec2], curve: F) -> f32 { //Furthest any curve sample lies from the polyline
+        let dist = |p: Vec2, a: Vec2, b: Vec2| {
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let l = dx * dx + dy * dy;
+            let k = if l == 0. { 0. } else { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / l).clamp(0., 1.) };
+            (p.0 - a.0 - k * dx).hypot(p.1 - a.1 - k * dy)
+        };
+        (0..=1000).map(|i| {
+            let p = curve(i as f32 / 1000.);
+            points.windows(2).map(|w| dist(p, w[0], w[1])).fold(f32::MAX, f32::min)
+        }).fold(0., f32::max)
+    }
+
+    #[test]
+    fn cubic_within_tolerance() {
+        let (p0, p1, p2, p3) = ((0., 0.), (300., -200.), (-100., 400.), (500., 100.));
+        for &tol in &[2., 0.5, 0.1] {
+            let mut points = vec![p0];
+            points.extend(flatten_cubic(p0, p1, p2, p3, tol));
+            let eval = |t: f32| {
+                let u = 1. - t;
+                let (a, b, c, d) = (u * u * u, 3. * u * u * t, 3. * u * t * t, t * t * t);
+                (a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0, a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1)
+            };
+            assert!(max_error(&points, eval) <= tol + 1e-3);
+            assert_eq!(points[points.len() - 1], p3);
+        }
+    }
+
+    #[test]
+    fn straight_quad_is_one_segment() {
+        assert_eq!(flatten_quad((0., 0.), (5., 5.), (10., 10.), 0.25), vec![(10., 10.)]);
+    }
+}
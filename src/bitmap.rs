@@ -0,0 +1,370 @@
+use serde::ser::{Serialize, Serializer};
+use serde::de::{self, Deserialize, Deserializer, Visitor, MapAccess};
+use serde::de::value::MapAccessDeserializer;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use png;
+
+use color::PColor;
+use paint::Spread;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Filter { //How a bitmap is sampled between its pixels
+    Nearest,
+    #[default]
+    Bilinear,
+    Bicubic, //Catmull-Rom over the nearest 4x4 pixels; sharper than bilinear when enlarging
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Bitmap { //Image loaded from a PPM or PNG file, or embedded in the scene
+    path: Option<String>, //Saved scenes only keep the path of images loaded from files
+    width: u32,
+    height: u32,
+    pixels: Rc<Vec<PColor>>, //Row-major, shared between clones
+    opaque: bool, //Whether every pixel is, worked out once when the pixels are set
+}
+
+impl Bitmap {
+    pub fn try_new(width: u32, height: u32, pixels: Vec<PColor>) -> Option<Bitmap> { //Embedded, so saved along with the scene; None unless there's a pixel for every place
+        let n = width.checked_mul(height)?;
+        if pixels.len() != n as usize {
+            return None;
+        }
+        let opaque = pixels.iter().all(|c| c.is_opaque());
+        Some(Bitmap { path: None, width, height, pixels: Rc::new(pixels), opaque })
+    }
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Bitmap> { //Format chosen by extension, relative to the working directory
+        let path = path.as_ref();
+        let name = path.to_str().ok_or_else(|| invalid("image path isn't valid UTF-8"))?; //Kept as a string in saved scenes
+        let file = BufReader::new(File::open(path)?);
+        let (width, height, pixels) = match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => read_ppm(file)?,
+            Some("png") => read_png(file)?,
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, "expected a .png or .ppm file name")),
+        };
+        let bitmap = Bitmap::try_new(width, height, pixels).ok_or_else(|| invalid("image size doesn't match its pixels"))?;
+        Ok(Bitmap { path: Some(name.to_string()), ..bitmap })
+    }
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+    pub fn embed(&mut self) { //Saves the pixels with the scene from now on, rather than the path
+        self.path = None;
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn is_opaque(&self) -> bool {
+        self.opaque
+    }
+    pub fn texel(&self, x: i32, y: i32, spread: Spread) -> PColor { //Out of range coordinates are wrapped by the spread
+        if self.width == 0 || self.height == 0 {
+            return PColor(0, 0, 0, 0);
+        }
+        let x = wrap(x, self.width as i32, spread);
+        let y = wrap(y, self.height as i32, spread);
+        self.pixels[(y * self.width as i32 + x) as usize]
+    }
+    pub fn sample(&self, p: (f32, f32), filter: Filter, spread: Spread) -> PColor { //Point in pixels, with pixel centers at halves
+        match filter {
+            Filter::Nearest => self.texel(p.0.floor() as i32, p.1.floor() as i32, spread),
+            Filter::Bilinear => {
+                let (x, y) = (p.0 - 0.5, p.1 - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                PColor::mix(&[
+                    (self.texel(x0, y0, spread), (1. - fx) * (1. - fy)),
+                    (self.texel(x0 + 1, y0, spread), fx * (1. - fy)),
+                    (self.texel(x0, y0 + 1, spread), (1. - fx) * fy),
+                    (self.texel(x0 + 1, y0 + 1, spread), fx * fy),
+                ])
+            },
+            Filter::Bicubic => {
+                let (x, y) = (p.0 - 0.5, p.1 - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i32, y0 as i32);
+                let mut samples = Vec::with_capacity(16);
+                for j in -1..3 {
+                    for i in -1..3 {
+                        let w = catmull_rom(i as f32 - fx) * catmull_rom(j as f32 - fy);
+                        samples.push((self.texel(x0 + i, y0 + j, spread), w));
+                    }
+                }
+                PColor::mix(&samples) //Weights can be negative, so overshoot is clamped here
+            },
+        }
+    }
+}
+
+fn catmull_rom(d: f32) -> f32 { //Cubic convolution kernel with a = -0.5, for a pixel d away
+    let d = d.abs();
+    if d < 1. {
+        1.5 * d * d * d - 2.5 * d * d + 1.
+    } else if d < 2. {
+        -0.5 * d * d * d + 2.5 * d * d - 4. * d + 2.
+    } else {
+        0.
+    }
+}
+
+fn wrap(i: i32, n: i32, spread: Spread) -> i32 {
+    match spread {
+        Spread::Pad => i.clamp(0, n - 1),
+        Spread::Repeat => i.rem_euclid(n),
+        Spread::Reflect => {
+            let m = i.rem_euclid(2 * n);
+            if m >= n { 2 * n - 1 - m } else { m }
+        },
+    }
+}
+
+thread_local! {
+    static BASE: RefCell<Option<PathBuf>> = const { RefCell::new(None) }; //Directory of the scene file being loaded
+}
+
+pub(crate) fn relative_to<T, F: FnOnce() -> T>(dir: &Path, f: F) -> T { //Runs f with image paths read by it resolved against dir
+    let previous = BASE.with(|base| base.replace(Some(dir.to_path_buf())));
+    let result = f();
+    BASE.with(|base| base.replace(previous));
+    result
+}
+
+fn resolve(path: &str) -> PathBuf { //Absolute paths are kept as they are
+    BASE.with(|base| match *base.borrow() {
+        Some(ref dir) => dir.join(path),
+        None => PathBuf::from(path),
+    })
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub(crate) fn read_ppm<R: Read>(mut r: R) -> io::Result<(u32, u32, Vec<PColor>)> { //Binary P6 or plain P3, 8 bits per channel
+    let mut data = Vec::new();
+    r.read_to_end(&mut data)?;
+    let mut pos = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 { //Magic, width, height and maxval, separated by whitespace and comments
+        while pos < data.len() && (data[pos].is_ascii_whitespace() || data[pos] == b'#') {
+            if data[pos] == b'#' {
+                while pos < data.len() && data[pos] != b'\n' {
+                    pos += 1;
+                }
+            } else {
+                pos += 1;
+            }
+        }
+        let start = pos;
+        while pos < data.len() && !data[pos].is_ascii_whitespace() && data[pos] != b'#' {
+            pos += 1;
+        }
+        if start == pos {
+            return Err(invalid("truncated PPM header"));
+        }
+        header.push(String::from_utf8_lossy(&data[start..pos]).into_owned());
+    }
+    let number = |s: &str| s.parse::<u32>().map_err(|_| invalid("bad number in PPM header"));
+    let (width, height, max) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+    if max == 0 || max > 255 {
+        return Err(invalid("only 8-bit PPM images are supported"));
+    }
+    let n = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or_else(|| invalid("PPM image too large"))? as usize;
+    let channels: Vec<u32> = match header[0].as_str() {
+        "P6" => {
+            let body = &data[(pos + 1).min(data.len())..]; //A single whitespace byte ends the header
+            if body.len() < n {
+                return Err(invalid("truncated PPM data"));
+            }
+            body[..n].iter().map(|&c| c as u32).collect()
+        },
+        "P3" => {
+            let values = String::from_utf8_lossy(&data[pos..]).split_whitespace().take(n).map(number).collect::<io::Result<Vec<u32>>>()?;
+            if values.len() < n {
+                return Err(invalid("truncated PPM data"));
+            }
+            values
+        },
+        _ => return Err(invalid("not a P3 or P6 PPM image")),
+    };
+    let scale = |c: u32| (c.min(max) * 255 / max) as u8;
+    let pixels = channels.chunks(3).map(|c| PColor::rgb(scale(c[0]), scale(c[1]), scale(c[2]))).collect();
+    Ok((width, height, pixels))
+}
+
+pub(crate) fn read_png<R: BufRead + Seek>(r: R) -> io::Result<(u32, u32, Vec<PColor>)> {
+    let mut decoder = png::Decoder::new(r);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size().ok_or_else(|| invalid("PNG image too large"))?];
+    let info = reader.next_frame(&mut buf)?;
+    let data = &buf[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => data.chunks(4).map(|c| PColor(c[0], c[1], c[2], c[3])).collect(),
+        png::ColorType::Rgb => data.chunks(3).map(|c| PColor::rgb(c[0], c[1], c[2])).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks(2).map(|c| PColor(c[0], c[0], c[0], c[1])).collect(),
+        png::ColorType::Grayscale => data.iter().map(|&c| PColor::rgb(c, c, c)).collect(),
+        png::ColorType::Indexed => return Err(invalid("unexpanded palette in PNG image")),
+    };
+    Ok((info.width, info.height, pixels))
+}
+
+impl fmt::Debug for Bitmap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bitmap({:?}, {}x{})", self.path, self.width, self.height)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Embedded { //How an embedded bitmap is saved
+    width: u32,
+    height: u32,
+    pixels: String, //Hex, 8 digits per pixel in R, G, B, A order
+}
+
+impl Serialize for Bitmap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        match self.path {
+            Some(ref path) => serializer.serialize_str(path),
+            None => {
+                let pixels = self.pixels.iter().map(|c| format!("{:02x}{:02x}{:02x}{:02x}", c.0, c.1, c.2, c.3)).collect();
+                Embedded { width: self.width, height: self.height, pixels }.serialize(serializer)
+            },
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Bitmap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        struct BitmapVisitor;
+
+        impl<'de> Visitor<'de> for BitmapVisitor {
+            type Value = Bitmap;
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an image path or an embedded image")
+            }
+            fn visit_str<E>(self, path: &str) -> Result<Bitmap, E> where E: de::Error {
+                let resolved = resolve(path);
+                let bitmap = Bitmap::open(&resolved).map_err(|e| E::custom(format!("{}: {}", resolved.display(), e)))?;
+                Ok(Bitmap { path: Some(path.to_string()), ..bitmap }) //Saved again as written, so it stays relative to the scene
+            }
+            fn visit_map<M>(self, map: M) -> Result<Bitmap, M::Error> where M: MapAccess<'de> {
+                let e = Embedded::deserialize(MapAccessDeserializer::new(map))?;
+                let digits = e.pixels.as_bytes();
+                let n = e.width.checked_mul(e.height).and_then(|n| n.checked_mul(8)).ok_or_else(|| de::Error::custom("embedded image too large"))?;
+                if digits.len() != n as usize {
+                    return Err(de::Error::custom("embedded image size doesn't match its pixels"));
+                }
+                let byte = |i: usize| {
+                    let s = ::std::str::from_utf8(&digits[i..i + 2]).map_err(de::Error::custom)?;
+                    u8::from_str_radix(s, 16).map_err(de::Error::custom)
+                };
+                let mut pixels = Vec::with_capacity(digits.len() / 8);
+                for i in (0..digits.len()).step_by(8) {
+                    pixels.push(PColor(byte(i)?, byte(i + 2)?, byte(i + 4)?, byte(i + 6)?));
+                }
+                Bitmap::try_new(e.width, e.height, pixels).ok_or_else(|| de::Error::custom("embedded image size doesn't match its pixels"))
+            }
+        }
+
+        deserializer.deserialize_any(BitmapVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checker() -> Bitmap { //Black and white 2x2
+        let (b, w) = (PColor::rgb(0, 0, 0), PColor::rgb(255, 255, 255));
+        Bitmap::try_new(2, 2, vec![b, w, w, b]).unwrap()
+    }
+
+    #[test]
+    fn spread_wraps_texels() {
+        let bitmap = checker();
+        assert_eq!(bitmap.texel(2, 0, Spread::Repeat), bitmap.texel(0, 0, Spread::Repeat));
+        assert_eq!(bitmap.texel(2, 0, Spread::Reflect), bitmap.texel(1, 0, Spread::Reflect));
+        assert_eq!(bitmap.texel(-1, 0, Spread::Reflect), bitmap.texel(0, 0, Spread::Reflect));
+        assert_eq!(bitmap.texel(5, -3, Spread::Pad), bitmap.texel(1, 0, Spread::Pad));
+    }
+
+    #[test]
+    fn bilinear_blends_between_centers() {
+        let bitmap = checker();
+        assert_eq!(bitmap.sample((0.5, 0.5), Filter::Bilinear, Spread::Pad), PColor::rgb(0, 0, 0));
+        assert_eq!(bitmap.sample((1., 0.5), Filter::Bilinear, Spread::Pad), PColor::rgb(128, 128, 128));
+        assert_eq!(bitmap.sample((1., 0.5), Filter::Nearest, Spread::Pad), PColor::rgb(255, 255, 255));
+    }
+
+    #[test]
+    fn bicubic_interpolates_centers() {
+        let bitmap = checker();
+        for &(x, y) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let center = (x as f32 + 0.5, y as f32 + 0.5);
+            assert_eq!(bitmap.sample(center, Filter::Bicubic, Spread::Repeat), bitmap.texel(x, y, Spread::Repeat));
+        }
+    }
+
+    #[test]
+    fn embedded_round_trip() {
+        let bitmap = Bitmap::try_new(2, 1, vec![PColor(1, 2, 3, 4), PColor::rgb(255, 0, 128)]).unwrap();
+        let json = ::serde_json::to_string(&bitmap).unwrap();
+        assert_eq!(json, r#"{"width":2,"height":1,"pixels":"01020304ff0080ff"}"#);
+        assert_eq!(::serde_json::from_str::<Bitmap>(&json).unwrap(), bitmap);
+    }
+
+    #[test]
+    fn embedded_size_is_checked() {
+        assert!(Bitmap::try_new(2, 2, vec![PColor::rgb(0, 0, 0); 3]).is_none());
+        assert!(Bitmap::try_new(0x10000, 0x10000, Vec::new()).is_none());
+        let error = |json: &str| ::serde_json::from_str::<Bitmap>(json).unwrap_err().to_string();
+        assert!(error(r#"{"width":2,"height":1,"pixels":"01020304"}"#).starts_with("embedded image size doesn't match"));
+        assert!(error(r#"{"width":65536,"height":65536,"pixels":""}"#).starts_with("embedded image too large"));
+    }
+
+    #[test]
+    fn opened_images_save_their_path() {
+        let path = ::std::env::temp_dir().join(format!("bitmap-path-{}.ppm", ::std::process::id()));
+        ::std::fs::write(&path, b"P6 1 1 255\n\x01\x02\x03").unwrap();
+        let path = path.to_str().unwrap();
+        let bitmap = Bitmap::open(path).unwrap();
+        let json = ::serde_json::to_string(&bitmap).unwrap();
+        assert_eq!(json, ::serde_json::to_string(path).unwrap());
+        let loaded: Bitmap = ::serde_json::from_str(&json).unwrap();
+        ::std::fs::remove_file(path).unwrap();
+        assert_eq!(loaded, bitmap);
+        assert_eq!(loaded.path(), Some(path));
+        assert!(loaded.is_opaque());
+    }
+
+    #[test]
+    fn reads_ppm() {
+        let p3 = "P3\n# comment\n2 1\n15\n15 0 0  0 15 0\n";
+        let (w, h, pixels) = read_ppm(p3.as_bytes()).unwrap();
+        assert_eq!((w, h), (2, 1));
+        assert_eq!(pixels, vec![PColor::rgb(255, 0, 0), PColor::rgb(0, 255, 0)]);
+        let p6 = b"P6 1 1 255\n\x01\x02\x03";
+        assert_eq!(read_ppm(&p6[..]).unwrap().2, vec![PColor::rgb(1, 2, 3)]);
+    }
+
+    #[test]
+    fn rejects_bad_ppm() {
+        let message = |data: &[u8]| read_ppm(data).unwrap_err().to_string();
+        assert_eq!(message(b"P6 2 1 255\n\x01\x02\x03"), "truncated PPM data");
+        assert_eq!(message(b"P6 70000 70000 255\n"), "PPM image too large");
+        assert_eq!(message(b"P3 1 1 255\n1 2"), "truncated PPM data");
+    }
+}
@@ -0,0 +1,91 @@
+use serde::ser::{Serialize, Serializer, SerializeTuple};
+use serde::de::{self, Deserialize, Deserializer, Visitor, SeqAccess};
+
+use std::fmt;
+
+use framebuffer::Rgba;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PColor(pub u8, pub u8, pub u8, pub u8); //Red, green, blue, alpha; not premultiplied
+
+impl PColor {
+    pub fn rgb(r: u8, g: u8, b: u8) -> PColor {
+        PColor(r, g, b, 255)
+    }
+    pub fn rgba(self) -> Rgba {
+        (self.0, self.1, self.2, self.3)
+    }
+    pub fn is_opaque(&self) -> bool {
+        self.3 == 255
+    }
+    pub fn with_alpha(self, a: f32) -> PColor { //Scales the alpha, for opacity
+        PColor(self.0, self.1, self.2, (self.3 as f32 * a.clamp(0., 1.)).round() as u8)
+    }
+    pub fn premultiplied(self) -> [f32; 4] { //Channels in 0..1 with color scaled by alpha
+        let a = self.3 as f32 / 255.;
+        [self.0 as f32 / 255. * a, self.1 as f32 / 255. * a, self.2 as f32 / 255. * a, a]
+    }
+    pub fn from_premultiplied(c: [f32; 4]) -> PColor {
+        let a = c[3].clamp(0., 1.);
+        if a == 0. {
+            return PColor(0, 0, 0, 0);
+        }
+        let channel = |v: f32| (v / a * 255.).round().clamp(0., 255.) as u8;
+        PColor(channel(c[0]), channel(c[1]), channel(c[2]), (a * 255.).round() as u8)
+    }
+    pub fn mix(colors: &[(PColor, f32)]) -> PColor { //Weighted sum, premultiplied so transparent colors don't darken their neighbours
+        let mut sum = [0.; 4];
+        for &(c, w) in colors {
+            let p = c.premultiplied();
+            for i in 0..4 {
+                sum[i] += p[i] * w;
+            }
+        }
+        PColor::from_premultiplied(sum)
+    }
+}
+
+impl From<(u8, u8, u8)> for PColor {
+    fn from(c: (u8, u8, u8)) -> PColor {
+        PColor::rgb(c.0, c.1, c.2)
+    }
+}
+
+impl From<Rgba> for PColor {
+    fn from(c: Rgba) -> PColor {
+        PColor(c.0, c.1, c.2, c.3)
+    }
+}
+
+impl Serialize for PColor {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut tup = serializer.serialize_tuple(4)?;
+        tup.serialize_element(&self.0)?;
+        tup.serialize_element(&self.1)?;
+        tup.serialize_element(&self.2)?;
+        tup.serialize_element(&self.3)?;
+        tup.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PColor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        struct PColorVisitor;
+
+        impl<'de> Visitor<'de> for PColorVisitor {
+            type Value = PColor;
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("[r, g, b] or [r, g, b, a]")
+            }
+            fn visit_seq<V>(self, mut seq: V) -> Result<PColor, V::Error> where V: SeqAccess<'de> {
+                let r = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let g = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                let b = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(2, &self))?;
+                let a = seq.next_element()?.unwrap_or(255); //Scenes saved before alpha are opaque
+                Ok(PColor(r, g, b, a))
+            }
+        }
+
+        deserializer.deserialize_seq(PColorVisitor)
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use backend::Surface;
+use color::PColor;
+use framebuffer::Rgba;
+use util::{Clip, Point};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Composite { //Porter-Duff operators; source is the object, destination what is already drawn
+    Clear,
+    Source,
+    Destination,
+    #[default]
+    SourceOver,
+    DestinationOver,
+    SourceIn,
+    DestinationIn,
+    SourceOut,
+    DestinationOut,
+    SourceAtop,
+    DestinationAtop,
+    Xor,
+}
+
+impl Composite {
+    pub fn next(self) -> Composite { //In declaration order, wrapping around
+        let all = [
+            Composite::Clear, Composite::Source, Composite::Destination, Composite::SourceOver,
+            Composite::DestinationOver, Composite::SourceIn, Composite::DestinationIn, Composite::SourceOut,
+            Composite::DestinationOut, Composite::SourceAtop, Composite::DestinationAtop, Composite::Xor,
+        ];
+        let i = all.iter().position(|&c| c == self).unwrap();
+        all[(i + 1) % all.len()]
+    }
+    pub fn is_bounded(self) -> bool { //Whether it leaves the destination alone where there's no source; the others clear or cut it there too
+        !matches!(self, Composite::Clear | Composite::Source | Composite::SourceIn | Composite::DestinationIn | Composite::SourceOut | Composite::DestinationAtop)
+    }
+    pub fn apply(self, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] { //Premultiplied colors
+        let (sa, da) = (src[3], dst[3]);
+        //How much of the source and of the destination each operator keeps
+        let (fs, fd) = match self {
+            Composite::Clear => (0., 0.),
+            Composite::Source => (1., 0.),
+            Composite::Destination => (0., 1.),
+            Composite::SourceOver => (1., 1. - sa),
+            Composite::DestinationOver => (1. - da, 1.),
+            Composite::SourceIn => (da, 0.),
+            Composite::DestinationIn => (0., sa),
+            Composite::SourceOut => (1. - da, 0.),
+            Composite::DestinationOut => (0., 1. - sa),
+            Composite::SourceAtop => (da, 1. - sa),
+            Composite::DestinationAtop => (1. - da, sa),
+            Composite::Xor => (1. - da, 1. - sa),
+        };
+        [
+            src[0] * fs + dst[0] * fd,
+            src[1] * fs + dst[1] * fd,
+            src[2] * fs + dst[2] * fd,
+            sa * fs + da * fd,
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Blend { //Separable blend modes; how the source color is mixed with the color already drawn, before compositing
+    #[default]
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    Difference,
+    Exclusion,
+    Additive,
+}
+
+impl Blend {
+    pub fn next(self) -> Blend { //In declaration order, wrapping around
+        let all = [
+            Blend::Normal, Blend::Multiply, Blend::Screen, Blend::Overlay, Blend::Darken,
+            Blend::Lighten, Blend::Difference, Blend::Exclusion, Blend::Additive,
+        ];
+        let i = all.iter().position(|&b| b == self).unwrap();
+        all[(i + 1) % all.len()]
+    }
+    fn channel(self, b: f32, s: f32) -> f32 { //Backdrop and source channels, not premultiplied
+        match self {
+            Blend::Normal => s,
+            Blend::Multiply => b * s,
+            Blend::Screen => b + s - b * s,
+            Blend::Overlay => if b <= 0.5 { 2. * b * s } else { 1. - 2. * (1. - b) * (1. - s) }, //Hard light with the layers swapped
+            Blend::Darken => b.min(s),
+            Blend::Lighten => b.max(s),
+            Blend::Difference => (b - s).abs(),
+            Blend::Exclusion => b + s - 2. * b * s,
+            Blend::Additive => (b + s).min(1.),
+        }
+    }
+    pub fn apply(self, src: [f32; 4], dst: [f32; 4]) -> [f32; 4] { //Premultiplied; the source recolored where it overlaps the backdrop
+        let (sa, da) = (src[3], dst[3]);
+        if self == Blend::Normal || sa == 0. || da == 0. {
+            return src;
+        }
+        let mut out = [0., 0., 0., sa];
+        for i in 0..3 {
+            let (s, b) = (src[i] / sa, dst[i] / da);
+            out[i] = ((1. - da) * s + da * self.channel(b, s)) * sa;
+        }
+        out
+    }
+}
+
+#[derive(Default)]
+pub struct Layer { //Sparse offscreen surface that an object is drawn into before being composited as a whole
+    color: Rgba,
+    index: HashMap<Point, usize>,
+    pixels: Vec<(Point, [f32; 4])>, //Premultiplied, in the order first drawn
+}
+
+impl Layer {
+    pub fn new() -> Layer {
+        Layer::default()
+    }
+    fn paint(&mut self, p: Point, coverage: f32) {
+        let src = PColor::from(self.color).with_alpha(coverage).premultiplied();
+        match self.index.get(&p) {
+            Some(&i) => {
+                let dst = self.pixels[i].1;
+                self.pixels[i].1 = Composite::SourceOver.apply(src, dst);
+            },
+            None => {
+                self.index.insert(p, self.pixels.len());
+                self.pixels.push((p, src));
+            },
+        }
+    }
+    pub fn composite_onto<S: Surface>(self, surface: &mut S, opacity: f32, op: Composite, blend: Blend, clip: &Clip) { //Each pixel once, so overlaps within the object don't blend twice
+        for &(p, c) in &self.pixels {
+            surface.composite(p, PColor::from_premultiplied(c).with_alpha(opacity).rgba(), op, blend);
+        }
+        if !op.is_bounded() {
+            //Everywhere else in clip the source is transparent, which these operators still act on
+            let ((x0, y0), (x1, y1)) = *clip;
+            for y in y0..y1.saturating_add(1) {
+                for x in x0..x1.saturating_add(1) {
+                    if !self.index.contains_key(&(x, y)) {
+                        surface.composite((x, y), (0, 0, 0, 0), op, blend);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Surface for Layer {
+    fn set_color(&mut self, color: Rgba) {
+        self.color = color;
+    }
+    fn plot(&mut self, p: Point) {
+        self.paint(p, 1.);
+    }
+    fn plot_coverage(&mut self, p: Point, coverage: f32) {
+        self.paint(p, coverage);
+    }
+    fn clear(&mut self, _color: Rgba) {
+        self.index.clear();
+        self.pixels.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operators_on_opaque_and_clear() {
+        let red = [1., 0., 0., 1.];
+        let half_blue = [0., 0., 0.5, 0.5];
+        let empty = [0.; 4];
+        assert_eq!(Composite::SourceOver.apply(half_blue, red), [0.5, 0., 0.5, 1.]);
+        assert_eq!(Composite::DestinationOver.apply(half_blue, red), red);
+        assert_eq!(Composite::SourceIn.apply(half_blue, empty), empty);
+        assert_eq!(Composite::SourceIn.apply(half_blue, red), half_blue);
+        assert_eq!(Composite::DestinationOut.apply(half_blue, red), [0.5, 0., 0., 0.5]);
+        assert_eq!(Composite::SourceAtop.apply(half_blue, red), [0.5, 0., 0.5, 1.]);
+        assert_eq!(Composite::Xor.apply(red, red), empty);
+        assert_eq!(Composite::Clear.apply(red, red), empty);
+        //Bounded operators are exactly those a transparent source leaves the destination alone under
+        for op in (0..12).scan(Composite::Clear, |op, _| Some(::std::mem::replace(op, op.next()))) {
+            assert_eq!(op.is_bounded(), op.apply(empty, half_blue) == half_blue, "{:?}", op);
+        }
+    }
+
+    #[test]
+    fn blend_modes() {
+        let grey = [0.5, 0.5, 0.5, 1.];
+        let red = [1., 0., 0., 1.];
+        assert_eq!(Blend::Multiply.apply(red, grey), [0.5, 0., 0., 1.]);
+        assert_eq!(Blend::Screen.apply(red, grey), [1., 0.5, 0.5, 1.]);
+        assert_eq!(Blend::Difference.apply(red, grey), [0.5, 0.5, 0.5, 1.]);
+        assert_eq!(Blend::Additive.apply(red, grey), [1., 0.5, 0.5, 1.]);
+        assert_eq!(Blend::Darken.apply(red, grey), [0.5, 0., 0., 1.]);
+        //Over nothing the source keeps its own color
+        assert_eq!(Blend::Multiply.apply(red, [0.; 4]), red);
+        //Half covered backdrop blends half way
+        assert_eq!(Blend::Multiply.apply(red, [0.25, 0.25, 0.25, 0.5]), [0.75, 0., 0., 1.]);
+    }
+}
@@ -0,0 +1,184 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use png;
+
+use backend::Surface;
+use color::PColor;
+use composite::{Blend, Composite};
+use util::Point;
+
+pub type Rgba = (u8, u8, u8, u8);
+
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>, //Row-major, 4 bytes per pixel in R, G, B, A order
+    color: Rgba, //Used by the Surface impl
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Option<Framebuffer> { //None if the size doesn't fit in memory or in pixel coordinates
+        if width > i32::MAX as u32 || height > i32::MAX as u32 {
+            return None;
+        }
+        let len = (width as usize).checked_mul(height as usize)?.checked_mul(4)?;
+        Some(Framebuffer {
+            width,
+            height,
+            pixels: vec![0; len],
+            color: (255, 255, 255, 255),
+        })
+    }
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+    pub fn pitch(&self) -> usize {
+        self.width as usize * 4
+    }
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+    pub fn clear(&mut self, color: Rgba) {
+        for px in self.pixels.chunks_mut(4) {
+            px[0] = color.0;
+            px[1] = color.1;
+            px[2] = color.2;
+            px[3] = color.3;
+        }
+    }
+    fn index(&self, p: Point) -> Option<usize> {
+        if p.0 < 0 || p.1 < 0 || p.0 as u32 >= self.width || p.1 as u32 >= self.height {
+            return None;
+        }
+        Some((p.1 as usize * self.width as usize + p.0 as usize) * 4)
+    }
+    pub fn put_pixel(&mut self, p: Point, color: Rgba) {
+        if let Some(i) = self.index(p) {
+            self.pixels[i] = color.0;
+            self.pixels[i + 1] = color.1;
+            self.pixels[i + 2] = color.2;
+            self.pixels[i + 3] = color.3;
+        }
+    }
+    pub fn blend_pixel(&mut self, p: Point, color: Rgba, coverage: f32) { //Source-over with alpha scaled by coverage
+        self.composite_pixel(p, PColor::from(color).with_alpha(coverage).rgba(), Composite::SourceOver, Blend::Normal);
+    }
+    pub fn composite_pixel(&mut self, p: Point, color: Rgba, op: Composite, blend: Blend) {
+        if let Some(dst) = self.get_pixel(p) {
+            let dst = PColor::from(dst).premultiplied();
+            let out = op.apply(blend.apply(PColor::from(color).premultiplied(), dst), dst);
+            self.put_pixel(p, PColor::from_premultiplied(out).rgba());
+        }
+    }
+    pub fn get_pixel(&self, p: Point) -> Option<Rgba> {
+        self.index(p).map(|i| (self.pixels[i], self.pixels[i + 1], self.pixels[i + 2], self.pixels[i + 3]))
+    }
+    pub fn blit(&mut self, src: &Framebuffer, at: Point) { //Copies src with its top-left at `at`, clipped to self
+        let x0 = at.0.max(0);
+        let x1 = at.0.saturating_add(src.width as i32).min(self.width as i32);
+        if x0 >= x1 {
+            return;
+        }
+        for y in at.1.max(0)..at.1.saturating_add(src.height as i32).min(self.height as i32) {
+            let d = self.index((x0, y)).unwrap();
+            let s = src.index((x0 - at.0, y - at.1)).unwrap();
+            let n = (x1 - x0) as usize * 4;
+            self.pixels[d..d + n].copy_from_slice(&src.pixels[s..s + n]);
+        }
+    }
+    pub fn write_ppm<W: Write>(&self, w: &mut W) -> io::Result<()> { //Binary P6, alpha is dropped
+        write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+        let rgb: Vec<u8> = self.pixels.chunks(4).flat_map(|px| px[..3].to_vec()).collect();
+        w.write_all(&rgb)
+    }
+    pub fn write_png<W: Write>(&self, w: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(w, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> { //Format chosen by extension
+        let path = path.as_ref();
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("ppm") => self.write_ppm(&mut BufWriter::new(File::create(path)?)),
+            Some("png") => self.write_png(BufWriter::new(File::create(path)?)),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "expected a .png or .ppm file name")),
+        }
+    }
+}
+
+impl Surface for Framebuffer {
+    fn set_color(&mut self, color: Rgba) {
+        self.color = color;
+    }
+    fn plot(&mut self, p: Point) {
+        let color = self.color;
+        self.put_pixel(p, color);
+    }
+    fn plot_span(&mut self, x0: i32, x1: i32, y: i32) {
+        if y < 0 || y as u32 >= self.height {
+            return;
+        }
+        let color = self.color;
+        for x in x0.max(0)..x1.min(self.width as i32 - 1) + 1 {
+            self.put_pixel((x, y), color);
+        }
+    }
+    fn plot_coverage(&mut self, p: Point, coverage: f32) {
+        let color = self.color;
+        self.blend_pixel(p, color, coverage);
+    }
+    fn composite(&mut self, p: Point, color: Rgba, op: Composite, blend: Blend) {
+        self.composite_pixel(p, color, op, blend);
+    }
+    fn clear(&mut self, color: Rgba) {
+        Framebuffer::clear(self, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_is_checked() {
+        assert!(Framebuffer::new(u32::MAX, u32::MAX).is_none());
+        assert!(Framebuffer::new(1 << 31, 1).is_none());
+        assert_eq!(Framebuffer::new(0, 7).map(|fb| fb.pixels().len()), Some(0));
+    }
+
+    #[test]
+    fn pixels_clear_and_blit() {
+        let (red, blue) = ((255, 0, 0, 255), (0, 0, 255, 255));
+        let mut fb = Framebuffer::new(4, 3).unwrap();
+        fb.clear(red);
+        assert!(fb.pixels().chunks(4).all(|px| px == [255, 0, 0, 255]));
+        fb.put_pixel((3, 2), blue);
+        fb.put_pixel((4, 0), blue); //Outside, ignored
+        fb.put_pixel((-1, 0), blue);
+        assert_eq!(fb.get_pixel((3, 2)), Some(blue));
+        assert_eq!(fb.get_pixel((0, 0)), Some(red));
+        assert_eq!(fb.get_pixel((4, 0)), None);
+        assert_eq!(fb.get_pixel((0, -1)), None);
+        //A 2 by 2 source hanging off the top-left corner only copies its bottom-right pixel
+        let mut src = Framebuffer::new(2, 2).unwrap();
+        src.clear(blue);
+        src.put_pixel((0, 1), red);
+        let mut dst = Framebuffer::new(3, 3).unwrap();
+        dst.blit(&src, (-1, -1));
+        assert_eq!(dst.get_pixel((0, 0)), Some(blue));
+        assert_eq!(dst.get_pixel((1, 0)), Some((0, 0, 0, 0)));
+        assert_eq!(dst.get_pixel((0, 1)), Some((0, 0, 0, 0)));
+        dst.blit(&src, (2, 1));
+        assert_eq!((dst.get_pixel((2, 1)), dst.get_pixel((2, 2))), (Some(blue), Some(red)));
+        dst.blit(&src, (i32::MAX, i32::MAX));
+        dst.blit(&src, (i32::MIN, 0));
+    }
+}
@@ -0,0 +1,72 @@
+#![cfg_attr(feature = "test", feature(test))]
+#[cfg(feature = "test")]
+extern crate test;
+
+#[cfg(feature = "sdl")]
+extern crate sdl2;
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+extern crate serde_json;
+extern crate png;
+
+pub mod util;
+pub mod transform;
+pub mod color;
+pub mod composite;
+pub mod stroke;
+pub mod bezier;
+pub mod path;
+pub mod paint;
+pub mod bitmap;
+pub mod scene;
+pub mod framebuffer;
+pub mod backend;
+#[cfg(feature = "sdl")]
+pub mod sdl_backend;
+
+pub use util::{Point, Clip, FillRule, VecExt};
+pub use scene::{Scene, Object, Shape, Fill, ArcKind, Pivot, ObjectList, Line, Vertex};
+pub use transform::{Angle, Transform};
+pub use stroke::{Stroke, LineCap, LineJoin};
+pub use bezier::Curve;
+pub use path::PathCommand;
+pub use paint::{Paint, Spread, Stop};
+pub use bitmap::{Bitmap, Filter};
+pub use framebuffer::{Framebuffer, Rgba};
+pub use color::PColor;
+pub use composite::{Blend, Composite, Layer};
+pub use backend::Surface;
+
+#[cfg(all(test, feature = "test"))]
+mod tests {
+    use util::*;
+    use test::Bencher;
+    #[bench]
+    fn thing(b: &mut Bencher) {
+        let mut points: Vec<Point> = Vec::new();
+        for y in 0..1000 {
+            points.extend(line((0, y), (1000, y), &UNCLIPPED));
+        }
+        b.iter(|| points.clone().scissor((200, 200), (400, 400)));
+    }
+    #[bench]
+    fn thing_iter(b: &mut Bencher) {
+        let mut points: Vec<Point> = Vec::new();
+        for y in 0..1000 {
+            points.extend(line((0, y), (1000, y), &UNCLIPPED));
+        }
+        b.iter(|| points.clone().scissor_iter((200, 200), (400, 400)));
+    }
+    #[bench]
+    fn thing_clip(b: &mut Bencher) {
+        b.iter(|| {
+            let mut points: Vec<Point> = Vec::new();
+            for y in 0..1000 {
+                points.extend(line((0, y), (1000, y), &((200, 200), (400, 400))));
+            }
+            points
+        });
+    }
+}
@@ -1,42 +1,75 @@
-#![cfg_attr(feature = "test", feature(test))]
-#[cfg(feature = "test")]
-extern crate test;
-
+extern crate rusterizer;
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 
-#[macro_use]
-extern crate serde_derive;
-extern crate serde;
-extern crate serde_json;
-
-use sdl2::pixels::Color;
+#[cfg(feature = "sdl")]
 use sdl2::event::Event;
+#[cfg(feature = "sdl")]
 use sdl2::keyboard::Keycode;
 
+#[cfg(feature = "sdl")]
 use std::time::Duration;
-use std::fs::File;
-use std::io::prelude::*;
 
-mod util;
-mod scene;
+use rusterizer::scene::{Scene,VIEW_SIZE};
+#[cfg(feature = "sdl")]
+use rusterizer::scene::{Object,Shape,ArcKind};
+#[cfg(feature = "sdl")]
+use rusterizer::transform::Angle;
+#[cfg(feature = "sdl")]
+use rusterizer::stroke::{LineCap, LineJoin};
+#[cfg(feature = "sdl")]
+use rusterizer::bezier::{Curve, default_tolerance};
+#[cfg(feature = "sdl")]
+use rusterizer::util::Point;
+#[cfg(feature = "sdl")]
+use rusterizer::color::PColor;
+#[cfg(feature = "sdl")]
+use rusterizer::bitmap::{Bitmap, Filter};
+#[cfg(feature = "sdl")]
+use rusterizer::backend::Surface;
+#[cfg(feature = "sdl")]
+use rusterizer::sdl_backend::SdlSurface;
 
-use scene::{Scene,Object,Shape};
+const USAGE: &str = "usage: rusterizer <scene.json> <output.png|output.ppm> [width height]";
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        if let Err(e) = render_headless(&args) {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            std::process::exit(1);
+        }
+        return;
+    }
+    run_editor();
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_editor() {
+    eprintln!("built without the `sdl` feature, only headless rendering is available");
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+#[cfg(feature = "sdl")]
+fn run_editor() {
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem.window("rusterizer", 1200, 1200)
+    let window = video_subsystem.window("rusterizer", VIEW_SIZE.0, VIEW_SIZE.1)
         .position_centered()
         .build()
         .unwrap();
-    let mut canvas: sdl2::render::Canvas<sdl2::video::Window> = window.into_canvas().accelerated().present_vsync().build().unwrap();
+    let canvas: sdl2::render::Canvas<sdl2::video::Window> = window.into_canvas().accelerated().present_vsync().build().unwrap();
     let mut event_pump = sdl_context.event_pump().unwrap();
+    let texture_creator = canvas.texture_creator();
+    let mut surface = SdlSurface::new(canvas, &texture_creator, VIEW_SIZE.0, VIEW_SIZE.1);
 
     let mut scene = Scene::new();
     //let letter = Object::new(Shape::for_letter('A'), (100, 100));
     //let poly = Object::new(Shape::Polygon(vec![(100, 100), (200, 200), (100, 200)]), (50, 50));
     let mut command_st = Object::new(Shape::Letters("".to_string()), (50, 50));
-    let blel = Object::new(Shape::Circle { height: 50, width: 100 }, (300, 300) );
+    let blel = Object::new(Shape::Circle { height: 50, width: 100, rotation: Angle::default() }, (300, 300) );
 
     scene.objects.extend(vec!(command_st.clone(), blel));
 
@@ -68,10 +101,26 @@ fn main() {
                                 continue;
                             }
                             match parts[0] {
-                                "ellipse" if parts.len() == 3 => {
+                                "ellipse" if parts.len() == 3 || parts.len() == 4 => {
                                     let x = parts[1].parse().unwrap();
                                     let y = parts[2].parse().unwrap();
-                                    scene.objects.extend(vec!(Object::with_color(Shape::Circle { height: x, width: y }, (100, 100), (255, 0, 0))));
+                                    let rotation = Angle::degrees(parts.get(3).map_or(0., |a| a.parse().unwrap()));
+                                    scene.objects.extend(vec!(Object::with_color(Shape::Circle { height: x, width: y, rotation }, (100, 100), (255, 0, 0))));
+                                },
+                                "arc" | "pie" | "chord" if parts.len() == 5 => {
+                                    let kind = match parts[0] {
+                                        "pie" => ArcKind::Pie,
+                                        "chord" => ArcKind::Chord,
+                                        _ => ArcKind::Open,
+                                    };
+                                    let shape = Shape::Arc {
+                                        width: parts[1].parse().unwrap(),
+                                        height: parts[2].parse().unwrap(),
+                                        start: Angle::degrees(parts[3].parse().unwrap()),
+                                        end: Angle::degrees(parts[4].parse().unwrap()),
+                                        kind,
+                                    };
+                                    scene.objects.extend(vec!(Object::with_color(shape, (100, 100), (255, 0, 0))));
                                 },
                                 "rect" if parts.len() == 5 => {
                                     let x0 = parts[1].parse().unwrap();
@@ -80,6 +129,44 @@ fn main() {
                                     let y1 = parts[4].parse().unwrap();
                                     scene.objects.extend(vec!(Object::with_color(Shape::Rect((x0, y0), (x1, y1)), (100, 100), (255, 0, 0))));
                                 },
+                                "roundrect" if parts.len() == 6 || parts.len() == 9 => {
+                                    let n: Vec<i32> = parts[1..].iter().map(|p| p.parse().unwrap()).collect();
+                                    let radii = if n.len() == 5 { [n[4]; 4] } else { [n[4], n[5], n[6], n[7]] };
+                                    scene.objects.extend(vec!(Object::with_color(Shape::RoundRect((n[0], n[1]), (n[2], n[3]), radii), (100, 100), (255, 0, 0))));
+                                },
+                                "triangle" if parts.len() == 7 => {
+                                    let p: Vec<Point> = parts[1..].chunks(2).map(|c| (c[0].parse().unwrap(), c[1].parse().unwrap())).collect();
+                                    let vertices = vec![(p[0], PColor::rgb(255, 0, 0)), (p[1], PColor::rgb(0, 255, 0)), (p[2], PColor::rgb(0, 0, 255))];
+                                    scene.objects.extend(vec!(Object::with_color(Shape::Triangles(vertices), (100, 100), (255, 0, 0))));
+                                },
+                                "polygon" if parts.len() >= 7 && parts.len() % 2 == 1 => {
+                                    let corners = parts[1..].chunks(2).map(|c| (c[0].parse().unwrap(), c[1].parse().unwrap())).collect();
+                                    scene.objects.extend(vec!(Object::with_color(Shape::Polygon(corners), (100, 100), (255, 0, 0))));
+                                },
+                                "bezier" if parts.len() == 7 || parts.len() == 9 => {
+                                    let p: Vec<Point> = parts[1..].chunks(2).map(|c| (c[0].parse().unwrap(), c[1].parse().unwrap())).collect();
+                                    let curve = if p.len() == 3 { Curve::Quad(p[1], p[2]) } else { Curve::Cubic(p[1], p[2], p[3]) };
+                                    let shape = Shape::Bezier { start: p[0], curves: vec![curve], tolerance: default_tolerance() };
+                                    scene.objects.extend(vec!(Object::with_color(shape, (100, 100), (255, 0, 0))));
+                                },
+                                "image" if parts.len() == 2 || (parts.len() == 3 && parts[2] == "embed") => {
+                                    match Bitmap::open(parts[1]) {
+                                        Ok(mut image) => {
+                                            if parts.len() == 3 {
+                                                image.embed();
+                                            }
+                                            let shape = Shape::Image { image, filter: Filter::Bilinear };
+                                            scene.objects.extend(vec!(Object::with_color(shape, (100, 100), (255, 0, 0))));
+                                        },
+                                        Err(_) => invalid = true,
+                                    }
+                                },
+                                "pattern" if parts.len() == 2 || (parts.len() == 3 && parts[2] == "stretch") => {
+                                    match (Bitmap::open(parts[1]), scene.objects[scene.selected_object].try_borrow_mut()) {
+                                        (Ok(image), Ok(mut object)) => object.set_pattern(image, parts.len() == 3),
+                                        _ => invalid = true, //Unreadable image, or the command line itself is selected
+                                    }
+                                },
                                 _ => {
                                     invalid = true;
                                 }
@@ -138,17 +225,17 @@ fn main() {
                 }
 
                 Event::KeyDown { keycode: Some(Keycode::LeftBracket), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
-                    scene.rotation -= 3.;
+                    scene.rotation -= Angle::degrees(3.);
                 }
                 Event::KeyDown { keycode: Some(Keycode::RightBracket), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
-                    scene.rotation += 3.;
+                    scene.rotation += Angle::degrees(3.);
                 }
 
                 Event::KeyDown { keycode: Some(Keycode::LeftBracket), .. } => {
-                    scene.objects[scene.selected_object].borrow_mut().rotation -= 3.;
+                    scene.objects[scene.selected_object].borrow_mut().rotation -= Angle::degrees(3.);
                 }
                 Event::KeyDown { keycode: Some(Keycode::RightBracket), .. } => {
-                    scene.objects[scene.selected_object].borrow_mut().rotation += 3.;
+                    scene.objects[scene.selected_object].borrow_mut().rotation += Angle::degrees(3.);
                 }
 
 
@@ -160,6 +247,77 @@ fn main() {
                     scene.objects.extend(locs.into_iter());
                 }
 
+                Event::KeyDown { keycode: Some(Keycode::F), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().cycle_fill();
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::H), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().cycle_paint();
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::A), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.antialias = !obj.antialias;
+                }
+                Event::KeyDown { keycode: Some(Keycode::O), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.opacity = if obj.opacity <= 0.25 { 1. } else { obj.opacity - 0.25 };
+                }
+                Event::KeyDown { keycode: Some(Keycode::M), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().cycle_composite();
+                }
+                Event::KeyDown { keycode: Some(Keycode::B), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().cycle_blend();
+                }
+                Event::KeyDown { keycode: Some(Keycode::G), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.antialias = !scene.antialias;
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::Period), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().stroke.width += 1.;
+                }
+                Event::KeyDown { keycode: Some(Keycode::Comma), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.stroke.width = (obj.stroke.width - 1.).max(1.);
+                }
+                Event::KeyDown { keycode: Some(Keycode::K), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.stroke.cap = match obj.stroke.cap {
+                        LineCap::Butt => LineCap::Round,
+                        LineCap::Round => LineCap::Square,
+                        LineCap::Square => LineCap::Butt,
+                    };
+                }
+                Event::KeyDown { keycode: Some(Keycode::J), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.stroke.join = match obj.stroke.join {
+                        LineJoin::Miter => LineJoin::Round,
+                        LineJoin::Round => LineJoin::Bevel,
+                        LineJoin::Bevel => LineJoin::Miter,
+                    };
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::D), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let mut obj = scene.objects[scene.selected_object].borrow_mut();
+                    obj.stroke.dash = match obj.stroke.dash.len() {
+                        0 => vec![12., 6.],
+                        2 if obj.stroke.dash[0] > 2. => vec![2., 6.],
+                        _ => Vec::new(),
+                    };
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::R), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    scene.objects[scene.selected_object].borrow_mut().cycle_pivot();
+                }
+
+                Event::KeyDown { keycode: Some(Keycode::P), keymod, .. } if keymod.contains(sdl2::keyboard::LCTRLMOD) => {
+                    let fb = scene.render(surface.fb.width(), surface.fb.height()).expect("the window already has a framebuffer this size");
+                    match fb.save("saved_drawing.png") {
+                        Ok(()) => println!("Exported view to saved_drawing.png"),
+                        Err(e) => println!("Export failed: {}", e),
+                    }
+                }
+
                 Event::KeyDown { keycode: Some(Keycode::Num0), keymod, .. } |
                 Event::KeyDown { keycode: Some(Keycode::Num1), keymod, .. } |
                 Event::KeyDown { keycode: Some(Keycode::Num2), keymod, .. } |
@@ -192,15 +350,10 @@ fn main() {
                     }
                 }
                 Event::KeyDown { keycode: Some(Keycode::Down), .. } => {
-                    let serial = serde_json::to_string(&scene).unwrap();
-                    let mut file = File::create("saved_drawing.json").unwrap();
-                    file.write_all(&serial.as_bytes()).unwrap();
+                    scene.save("saved_drawing.json").unwrap();
                 }
                 Event::KeyDown { keycode: Some(Keycode::Up), .. } => {
-                    let mut file = File::open("saved_drawing.json").unwrap();
-                    let mut contents = String::new();
-                    file.read_to_string(&mut contents).unwrap();
-                    scene = serde_json::from_str(&contents).unwrap();
+                    scene = Scene::load("saved_drawing.json").unwrap();
                     command_st = scene.objects.iter().cloned().filter(|x| if let Shape::Letters(_) = x.borrow().shape { true } else { false }).next().unwrap();
                 }
                 Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
@@ -230,14 +383,9 @@ fn main() {
                 _ => {}
             }
         }
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
-        canvas.clear();
-        //st.position.0 +=1;
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-
-        scene.draw(&mut canvas);
-        
-        canvas.present();
+        surface.clear((0, 0, 0, 255));
+        scene.draw(&mut surface);
+        surface.present();
         /*let error = ::sdl2::get_error();
         if error != "" {
             println!("{}", error);
@@ -246,24 +394,17 @@ fn main() {
     }
 }
 
-#[cfg(all(test, feature = "test"))]
-mod tests {
-    use super::*;
-    use test::Bencher;
-    #[bench]
-    fn thing(b: &mut Bencher) {
-        let mut points: Vec<Point> = Vec::new();
-        for y in 0..1000 {
-            points.extend(line((0, y), (1000, y)));
-        }
-        b.iter(|| points.clone().scissor((200, 200), (400, 400)));
-    }
-    #[bench]
-    fn thing_iter(b: &mut Bencher) {
-        let mut points: Vec<Point> = Vec::new();
-        for y in 0..1000 {
-            points.extend(line((0, y), (1000, y)));
+fn render_headless(args: &[String]) -> Result<(), String> {
+    let (input, output, size) = match args.len() {
+        2 => (&args[0], &args[1], VIEW_SIZE),
+        4 => {
+            let w = args[2].parse().map_err(|_| format!("invalid width: {}", args[2]))?;
+            let h = args[3].parse().map_err(|_| format!("invalid height: {}", args[3]))?;
+            (&args[0], &args[1], (w, h))
         }
-        b.iter(|| points.clone().scissor_iter((200, 200), (400, 400)));
-    }
-}
\ No newline at end of file
+        _ => return Err("wrong number of arguments".to_string()),
+    };
+    let scene = Scene::load(input).map_err(|e| format!("{}: {}", input, e))?;
+    let fb = scene.render(size.0, size.1).ok_or_else(|| format!("image too large: {}x{}", size.0, size.1))?;
+    fb.save(output).map_err(|e| format!("{}: {}", output, e))
+}
@@ -0,0 +1,151 @@
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Spread { //What a gradient does past its first and last stops
+    #[default]
+    Pad, //Holds the end colors
+    Repeat, //Starts over from the first stop
+    Reflect, //Runs back and forth
+}
+
+use bitmap::{Bitmap, Filter};
+use color::PColor;
+use transform::Transform;
+
+pub type Stop = (f32, PColor); //Offset along the gradient, usually 0..1, and color
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub enum Paint { //Coordinates are in the object's own space, so gradients move with it
+    #[default]
+    Solid, //The fill's color
+    Linear {
+        start: (f32, f32), //Offset 0
+        end: (f32, f32), //Offset 1
+        stops: Vec<Stop>,
+        #[serde(default)]
+        spread: Spread,
+    },
+    Radial {
+        center: (f32, f32), //Offset 0
+        radius: f32, //Offset 1 on the circle this far from the center
+        stops: Vec<Stop>,
+        #[serde(default)]
+        spread: Spread,
+    },
+    Pattern {
+        image: Bitmap,
+        #[serde(default = "Transform::identity")]
+        transform: Transform, //From image pixels to object space; scaling it stretches the image
+        #[serde(default)]
+        spread: Spread, //Pad stretches the image once, repeat and reflect tile it
+        #[serde(default)]
+        filter: Filter,
+    },
+}
+
+impl Paint {
+    pub fn is_solid(&self) -> bool {
+        matches!(self, Paint::Solid)
+    }
+    pub fn is_opaque(&self) -> bool {
+        match self {
+            Paint::Solid => true,
+            Paint::Linear { stops, .. } | Paint::Radial { stops, .. } => stops.iter().all(|s| s.1.is_opaque()),
+            Paint::Pattern { image, .. } => image.is_opaque(),
+        }
+    }
+    pub fn color_at(&self, p: (f32, f32), solid: PColor) -> PColor { //Object space point
+        self.colors_at(&[p], solid)[0]
+    }
+    pub fn colors_at(&self, points: &[(f32, f32)], solid: PColor) -> Vec<PColor> { //Object space points; a pattern's transform is inverted once for all of them
+        let pattern = match self {
+            Paint::Pattern { transform, .. } => transform.invert(),
+            _ => None,
+        };
+        points.iter().map(|&p| match self {
+            Paint::Solid => solid,
+            Paint::Linear { start, end, stops, spread } => {
+                let d = (end.0 - start.0, end.1 - start.1);
+                let len = d.0 * d.0 + d.1 * d.1;
+                let t = if len == 0. { 0. } else { ((p.0 - start.0) * d.0 + (p.1 - start.1) * d.1) / len };
+                sample(stops, spread_offset(t, *spread))
+            },
+            Paint::Radial { center, radius, stops, spread } => {
+                let r = (p.0 - center.0).hypot(p.1 - center.1);
+                let t = if *radius == 0. { 1. } else { r / radius };
+                sample(stops, spread_offset(t, *spread))
+            },
+            Paint::Pattern { image, spread, filter, .. } => match pattern {
+                Some(inverse) => image.sample(inverse.apply(p), *filter, *spread),
+                None => PColor(0, 0, 0, 0),
+            },
+        }).collect()
+    }
+}
+
+fn spread_offset(t: f32, spread: Spread) -> f32 {
+    match spread {
+        Spread::Pad => t.clamp(0., 1.),
+        Spread::Repeat => t - t.floor(),
+        Spread::Reflect => {
+            let m = t - (t / 2.).floor() * 2.;
+            if m > 1. { 2. - m } else { m }
+        },
+    }
+}
+
+fn sample(stops: &[Stop], t: f32) -> PColor { //Stops are expected in increasing offset order
+    let first = match stops.first() {
+        Some(s) => s,
+        None => return PColor(0, 0, 0, 0),
+    };
+    if t <= first.0 {
+        return first.1;
+    }
+    for w in stops.windows(2) {
+        let ((o0, c0), (o1, c1)) = (w[0], w[1]);
+        if t <= o1 {
+            let k = if o1 > o0 { (t - o0) / (o1 - o0) } else { 1. };
+            return PColor::mix(&[(c0, 1. - k), (c1, k)]);
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_modes() {
+        let stops = vec![(0., PColor::rgb(0, 0, 0)), (1., PColor::rgb(200, 100, 0))];
+        let paint = |spread| Paint::Linear { start: (0., 0.), end: (10., 0.), stops: stops.clone(), spread };
+        let solid = PColor::rgb(0, 0, 0);
+        assert_eq!(paint(Spread::Pad).color_at((5., 3.), solid), PColor::rgb(100, 50, 0));
+        assert_eq!(paint(Spread::Pad).color_at((25., 0.), solid), PColor::rgb(200, 100, 0));
+        assert_eq!(paint(Spread::Pad).color_at((-5., 0.), solid), PColor::rgb(0, 0, 0));
+        assert_eq!(paint(Spread::Repeat).color_at((12.5, 0.), solid), PColor::rgb(50, 25, 0));
+        assert_eq!(paint(Spread::Reflect).color_at((17.5, 0.), solid), PColor::rgb(50, 25, 0));
+        assert_eq!(paint(Spread::Reflect).color_at((-2.5, 0.), solid), PColor::rgb(50, 25, 0));
+    }
+
+    #[test]
+    fn pattern_maps_through_its_transform() {
+        let (b, w) = (PColor::rgb(0, 0, 0), PColor::rgb(255, 255, 255));
+        let image = Bitmap::try_new(2, 2, vec![b, w, w, b]).unwrap();
+        let paint = |spread| Paint::Pattern { image: image.clone(), transform: Transform::scale(10., 10.), spread, filter: Filter::Nearest };
+        let solid = PColor::rgb(1, 2, 3);
+        assert_eq!(paint(Spread::Pad).color_at((5., 5.), solid), b);
+        assert_eq!(paint(Spread::Pad).color_at((15., 5.), solid), w);
+        assert_eq!(paint(Spread::Pad).color_at((35., 5.), solid), w);
+        assert_eq!(paint(Spread::Repeat).colors_at(&[(25., 5.), (35., 5.), (35., 15.)], solid), vec![b, w, b]);
+        let flat = Paint::Pattern { image, transform: Transform::scale(0., 1.), spread: Spread::Pad, filter: Filter::Nearest };
+        assert_eq!(flat.color_at((0., 0.), solid), PColor(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn transparent_stop_keeps_color() {
+        //Fading to transparent mixes premultiplied, so the color doesn't pick up the transparent stop's black
+        let stops = vec![(0., PColor::rgb(200, 100, 0)), (1., PColor(0, 0, 0, 0))];
+        let paint = Paint::Radial { center: (0., 0.), radius: 10., stops, spread: Spread::Pad };
+        assert_eq!(paint.color_at((5., 0.), PColor::rgb(0, 0, 0)), PColor(200, 100, 0, 128));
+    }
+}
@@ -0,0 +1,166 @@
+use std::f32::consts::PI;
+
+use bezier::{arc_cubics, flatten_cubic, flatten_quad};
+use transform::{Angle, Transform};
+use util::Point;
+
+type Vec2 = (f32, f32);
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum PathCommand { //After SVG path data, in absolute coordinates
+    MoveTo(Point), //Starts a new subpath
+    LineTo(Point),
+    QuadTo(Point, Point), //Control, end
+    CubicTo(Point, Point, Point), //Control, control, end
+    ArcTo { radii: (f32, f32), rotation: Angle, large_arc: bool, sweep: bool, end: Point },
+    Close, //Joins back to the start of the subpath
+}
+
+fn to_f(p: Point) -> Vec2 {
+    (p.0 as f32, p.1 as f32)
+}
+
+pub fn flatten(commands: &[PathCommand], tolerance: f32, t: &Transform) -> Vec<(Vec<Vec2>, bool)> { //Screen space subpaths and whether each is closed
+    let mut subpaths: Vec<(Vec<Vec2>, bool)> = Vec::new();
+    let mut current: Vec<Vec2> = Vec::new();
+    let mut start = (0, 0); //Object space, where Close returns to
+    let mut from = (0, 0);
+    for command in commands {
+        if current.is_empty() {
+            current.push(t.apply(to_f(from)));
+        }
+        let last = current[current.len() - 1];
+        match *command {
+            PathCommand::MoveTo(p) => {
+                if current.len() > 1 {
+                    subpaths.push((current, false));
+                }
+                current = vec![t.apply(to_f(p))];
+                start = p;
+                from = p;
+            },
+            PathCommand::LineTo(p) => {
+                current.push(t.apply(to_f(p)));
+                from = p;
+            },
+            PathCommand::QuadTo(c, p) => {
+                current.extend(flatten_quad(last, t.apply(to_f(c)), t.apply(to_f(p)), tolerance));
+                from = p;
+            },
+            PathCommand::CubicTo(c0, c1, p) => {
+                current.extend(flatten_cubic(last, t.apply(to_f(c0)), t.apply(to_f(c1)), t.apply(to_f(p)), tolerance));
+                from = p;
+            },
+            PathCommand::ArcTo { radii, rotation, large_arc, sweep, end } => {
+                let cubics = endpoint_arc(to_f(from), radii, rotation, large_arc, sweep, to_f(end));
+                if cubics.is_empty() && from != end {
+                    current.push(t.apply(to_f(end))); //Degenerate radii draw a straight line
+                }
+                for c in cubics {
+                    let p0 = current[current.len() - 1];
+                    current.extend(flatten_cubic(p0, t.apply(c[1]), t.apply(c[2]), t.apply(c[3]), tolerance));
+                }
+                from = end;
+            },
+            PathCommand::Close => {
+                if current.len() > 1 {
+                    subpaths.push((current, true));
+                }
+                current = Vec::new();
+                from = start;
+            },
+        }
+    }
+    if current.len() > 1 {
+        subpaths.push((current, false));
+    }
+    subpaths
+}
+
+fn endpoint_arc(p0: Vec2, radii: Vec2, rotation: Angle, large_arc: bool, sweep: bool, p1: Vec2) -> Vec<[Vec2; 4]> { //SVG endpoint parameterization to center parameterization (SVG 1.1, F.6.5)
+    let (mut rx, mut ry) = (radii.0.abs(), radii.1.abs());
+    if rx == 0. || ry == 0. || p0 == p1 {
+        return Vec::new();
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let (hx, hy) = ((p0.0 - p1.0) / 2., (p0.1 - p1.1) / 2.);
+    let x = cos * hx + sin * hy;
+    let y = -sin * hx + cos * hy;
+    let lambda = (x * x) / (rx * rx) + (y * y) / (ry * ry);
+    if lambda > 1. { //Radii too small to reach the end point are scaled up just enough
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let num = rx * rx * ry * ry - rx * rx * y * y - ry * ry * x * x;
+    let den = rx * rx * y * y + ry * ry * x * x;
+    let mut coef = (num / den).max(0.).sqrt();
+    if large_arc == sweep {
+        coef = -coef;
+    }
+    let (cx, cy) = (coef * rx * y / ry, -coef * ry * x / rx);
+    let center = (cos * cx - sin * cy + (p0.0 + p1.0) / 2., sin * cx + cos * cy + (p0.1 + p1.1) / 2.);
+    let theta0 = ((y - cy) / ry).atan2((x - cx) / rx);
+    let theta1 = ((-y - cy) / ry).atan2((-x - cx) / rx);
+    let mut delta = theta1 - theta0;
+    if sweep && delta < 0. {
+        delta += 2. * PI;
+    } else if !sweep && delta > 0. {
+        delta -= 2. * PI;
+    }
+    let mut cubics = arc_cubics(center, (rx, ry), rotation, theta0, delta);
+    if let Some(c) = cubics.last_mut() {
+        c[3] = p1; //Land exactly on the end point despite rounding
+    }
+    cubics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arc_stays_on_circle() {
+        //Half circle of radius 50 from (0, 0) to (100, 0), centered at (50, 0)
+        let commands = vec![
+            PathCommand::MoveTo((0, 0)),
+            PathCommand::ArcTo { radii: (50., 50.), rotation: Angle::default(), large_arc: false, sweep: true, end: (100, 0) },
+        ];
+        let subpaths = flatten(&commands, 0.1, &Transform::identity());
+        assert_eq!(subpaths.len(), 1);
+        let points = &subpaths[0].0;
+        assert_eq!(points[points.len() - 1], (100., 0.));
+        for p in points {
+            assert!(((p.0 - 50.).hypot(p.1) - 50.).abs() < 0.2);
+            assert!(p.1 <= 0.01); //Sweeping clockwise on screen goes through the top
+        }
+    }
+
+    #[test]
+    fn flat_arc_after_line_is_straight() {
+        let commands = vec![
+            PathCommand::MoveTo((0, 0)),
+            PathCommand::LineTo((10, 0)),
+            PathCommand::ArcTo { radii: (0., 0.), rotation: Angle::default(), large_arc: false, sweep: true, end: (10, 10) },
+            PathCommand::ArcTo { radii: (5., 5.), rotation: Angle::default(), large_arc: false, sweep: true, end: (10, 10) }, //Ends where it starts, so adds nothing
+        ];
+        let subpaths = flatten(&commands, 0.25, &Transform::identity());
+        assert_eq!(subpaths, vec![(vec![(0., 0.), (10., 0.), (10., 10.)], false)]);
+    }
+
+    #[test]
+    fn close_starts_new_subpath() {
+        let commands = vec![
+            PathCommand::MoveTo((0, 0)),
+            PathCommand::LineTo((10, 0)),
+            PathCommand::LineTo((10, 10)),
+            PathCommand::Close,
+            PathCommand::LineTo((0, 20)),
+            PathCommand::MoveTo((5, 5)),
+        ];
+        let subpaths = flatten(&commands, 0.25, &Transform::identity());
+        assert_eq!(subpaths, vec![
+            (vec![(0., 0.), (10., 0.), (10., 10.)], true),
+            (vec![(0., 0.), (0., 20.)], false),
+        ]);
+    }
+}
@@ -4,15 +4,30 @@ use serde::de::{Deserialize, Deserializer, Visitor, SeqAccess};
 use std::cell::RefCell;
 use std::rc::Rc;
 use std::ops::{Deref, DerefMut};
+use std::collections::HashMap;
 use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
 
-use sdl2::render::{RenderTarget,Canvas};
-use sdl2::pixels::Color;
+use serde_json;
 
-use util::{VecExt,line,rect,ellipse,polygon,Point};
+use backend::Surface;
+use bitmap::{self, Bitmap, Filter};
+use color::PColor;
+use composite::{Blend, Composite, Layer};
+use bezier::{self, Curve, arc_cubics, flatten_cubic, default_tolerance};
+use paint::{Paint, Spread};
+use path::{self, PathCommand};
+use framebuffer::Framebuffer;
+use stroke::Stroke;
+use transform::{Angle, Transform};
+use util::{VecExt,clip_bounds,line,triangle,round_rect,fill_round_rect,ellipse,ellipse_arc,conic_ellipse,fill_conic_ellipse,polyline,line_aa,polygon_aa,polyline_aa,fill_rect,fill_ellipse,fill_polygon,fill_polygon_f,fill_loops_f,Clip,FillRule,Point};
 
-type PColor = (u8, u8, u8);
-type Line = (Point, Point);
+pub type Line = (Point, Point);
+pub type Vertex = (Point, PColor);
+
+pub const VIEW_SIZE: (u32, u32) = (1200, 1200); //Size of the editor window the scene is laid out in
 
 #[derive(Serialize, Deserialize)]
 pub struct Scene {
@@ -21,14 +36,17 @@ pub struct Scene {
     pub groups: ObjectList,
     pub camera: Point,
     pub scale: (f32, f32),
-    pub rotation: f32,
+    pub rotation: Angle,
+    #[serde(default)]
+    pub antialias: bool, //Antialias every object, regardless of its own setting
 }
 
+#[derive(Default)]
 pub struct ObjectList(Vec<Rc<RefCell<Object>>>);
 
 impl ObjectList {
-    fn new() -> ObjectList {
-        ObjectList(Vec::new())
+    pub fn new() -> ObjectList {
+        ObjectList::default()
     }
 }
 
@@ -80,15 +98,16 @@ impl<'de> Deserialize<'de> for ObjectList {
     }
 }
 
-impl Scene {
-    pub fn new() -> Scene {
+impl Default for Scene { //Ten empty groups, one per number key
+    fn default() -> Scene {
         let mut s = Scene {
             objects: ObjectList::new(),
             selected_object: 0,
             groups: ObjectList::new(),
             camera: (0, 0),
-            rotation: 0.,
+            rotation: Angle::default(),
             scale: (1., 1.),
+            antialias: false,
         };
         for _ in 0..10 {
             let o = Object::new(Shape::Group(ObjectList::new()), (0, 0));
@@ -97,31 +116,172 @@ impl Scene {
         }
         s
     }
-    pub fn draw<T>(&self, canvas: &mut Canvas<T>) where T: RenderTarget {
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Scene> { //JSON, as written by save; image paths in it are relative to its directory
+        let path = path.as_ref();
+        let file = BufReader::new(File::open(path)?);
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(bitmap::relative_to(dir, || serde_json::from_reader(file))?)
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        Ok(serde_json::to_writer(file, self)?)
+    }
+    pub fn view(&self) -> Transform { //Scene space to screen space
+        Transform::rotate(self.rotation) *
+            Transform::scale(self.scale.0, self.scale.1) *
+            Transform::translate(-self.camera.0 as f32, -self.camera.1 as f32)
+    }
+    pub fn draw<S: Surface>(&self, surface: &mut S) {
+        self.draw_view(surface, self.view(), &viewport(VIEW_SIZE.0, VIEW_SIZE.1), true);
+    }
+    pub fn render(&self, width: u32, height: u32) -> Option<Framebuffer> { //Current view resampled to the given resolution, without selection; None if it's too large for a framebuffer
+        let mut fb = Framebuffer::new(width, height)?;
+        Surface::clear(&mut fb, (0, 0, 0, 255));
+        let view_scale = Transform::scale(width as f32 / VIEW_SIZE.0 as f32, height as f32 / VIEW_SIZE.1 as f32);
+        self.draw_view(&mut fb, view_scale * self.view(), &viewport(width, height), false);
+        Some(fb)
+    }
+    fn draw_view<S: Surface>(&self, surface: &mut S, view: Transform, clip: &Clip, highlight: bool) {
         for (i, object) in self.objects.iter().enumerate() {
             let object = object.borrow();
-            canvas.set_draw_color(Color::RGB(object.color.0, object.color.1, object.color.2));
-            if self.selected_object == i {
-                canvas.set_draw_color(Color::RGB(255, 255, 0));
-            }
-            let mut points = object.draw();
-            points.translate(-self.camera.0, -self.camera.1);
-            points.scale(self.scale.0, self.scale.1);
-            points.rotate(self.rotation);
-            for point in points.into_iter() {
-                canvas.draw_point(point).unwrap();
+            let color = if highlight && self.selected_object == i { PColor::rgb(255, 255, 0) } else { object.color };
+            let antialias = self.antialias || object.antialias;
+            if object.is_opaque() && color.is_opaque() {
+                draw_object(surface, &object, &view, clip, color, antialias); //Straight onto the surface, nothing to blend
+            } else {
+                let mut layer = Layer::new();
+                draw_object(&mut layer, &object, &view, clip, color, antialias);
+                layer.composite_onto(surface, object.opacity, object.composite, object.blend, clip);
             }
         }
     }
 }
 
+fn viewport(width: u32, height: u32) -> Clip {
+    ((0, 0), (width as i32 - 1, height as i32 - 1))
+}
+
+fn draw_object<S: Surface>(surface: &mut S, object: &Object, view: &Transform, clip: &Clip, color: PColor, antialias: bool) {
+    if object.misses(view, clip) {
+        return;
+    }
+    if let Some(ref fill) = object.fill {
+        if fill.paint.is_solid() {
+            draw_spans(surface, object.draw_fill(view, clip), fill.color);
+        } else {
+            draw_shaded(surface, object.draw_paint(view, clip));
+        }
+    }
+    draw_shaded(surface, object.draw_shaded(view, clip));
+    if antialias {
+        draw_coverage(surface, object.draw_aa(view, clip), color);
+    } else {
+        draw_points(surface, object.draw(view, clip), color);
+    }
+}
+
+fn draw_points<S: Surface>(surface: &mut S, points: Vec<Point>, color: PColor) {
+    surface.set_color(color.rgba());
+    for point in points.into_iter() {
+        surface.plot(point);
+    }
+}
+
+fn draw_spans<S: Surface>(surface: &mut S, points: Vec<Point>, color: PColor) { //Runs of neighbouring pixels along a row go out as one span
+    surface.set_color(color.rgba());
+    let mut run: Option<(i32, i32, i32)> = None; //Row, first and last x
+    for (x, y) in points.into_iter() {
+        run = match run {
+            Some((row, x0, x1)) if row == y && x == x1 + 1 => Some((row, x0, x)),
+            Some((row, x0, x1)) => {
+                surface.plot_span(x0, x1, row);
+                Some((y, x, x))
+            },
+            None => Some((y, x, x)),
+        };
+    }
+    if let Some((row, x0, x1)) = run {
+        surface.plot_span(x0, x1, row);
+    }
+}
+
+fn draw_coverage<S: Surface>(surface: &mut S, points: Vec<(Point, f32)>, color: PColor) {
+    surface.set_color(color.rgba());
+    for (point, coverage) in points.into_iter() {
+        surface.plot_coverage(point, coverage);
+    }
+}
+
+fn draw_shaded<S: Surface>(surface: &mut S, points: Vec<(Point, PColor)>) {
+    for (point, color) in points.into_iter() {
+        surface.set_color(color.rgba());
+        surface.plot(point);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Fill {
+    pub color: PColor,
+    pub rule: FillRule,
+    #[serde(default)]
+    pub paint: Paint,
+}
+
+impl Fill {
+    pub fn solid(color: PColor) -> Fill { //Even-odd, the first rule the editor cycles to
+        Fill { color, rule: FillRule::EvenOdd, paint: Paint::Solid }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum ArcKind {
+    #[default]
+    Open, //Filled as a chord
+    Pie, //Closed through the center
+    Chord, //Closed by a straight line between the ends
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Pivot { //Fixed point of an object's rotation, shear and scale
+    #[default]
+    Origin,
+    Center, //Of the shape's bounding box
+    Point(f32, f32), //In object space
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct Object {
     pub shape: Shape,
     pub position: Point,
     pub scale: (f32, f32),
-    pub rotation: f32,
-    color: PColor,
+    pub rotation: Angle,
+    #[serde(default)]
+    pub shear: (f32, f32),
+    #[serde(default)]
+    pub pivot: Pivot,
+    pub color: PColor, //Stroke color
+    #[serde(default)]
+    pub fill: Option<Fill>,
+    #[serde(default)]
+    pub stroke: Stroke,
+    #[serde(default)]
+    pub antialias: bool,
+    #[serde(default = "opaque")]
+    pub opacity: f32, //Applied to the object as a whole, after its own parts are drawn over each other
+    #[serde(default)]
+    pub composite: Composite,
+    #[serde(default)]
+    pub blend: Blend,
+}
+
+fn opaque() -> f32 {
+    1.
 }
 
 impl Object {
@@ -130,70 +290,514 @@ impl Object {
             shape,
             position,
             scale: (1., 1.),
-            color: (0, 255, 255),
-            rotation: 0.,
+            color: PColor::rgb(0, 255, 255),
+            rotation: Angle::default(),
+            shear: (0., 0.),
+            pivot: Pivot::Origin,
+            fill: None,
+            stroke: Stroke::default(),
+            antialias: false,
+            opacity: 1.,
+            composite: Composite::SourceOver,
+            blend: Blend::Normal,
         }))
     }
-    pub fn with_color(shape: Shape, position: Point, color: PColor) -> Rc<RefCell<Object>> {
+    pub fn with_color<C: Into<PColor>>(shape: Shape, position: Point, color: C) -> Rc<RefCell<Object>> {
         Rc::new(RefCell::new(Object {
             shape,
             position,
             scale: (1., 1.),
-            rotation: 0.,
-            color,
+            rotation: Angle::default(),
+            shear: (0., 0.),
+            pivot: Pivot::Origin,
+            color: color.into(),
+            fill: None,
+            stroke: Stroke::default(),
+            antialias: false,
+            opacity: 1.,
+            composite: Composite::SourceOver,
+            blend: Blend::Normal,
         }))
     }
-    fn draw(&self) -> Vec<Point> {
-        let mut points = self.shape.draw(self.scale);
-        points.scale(self.scale.0, self.scale.1);
-        points.rotate(self.rotation);
-        points.translate(self.position.0, self.position.1);
+    pub fn cycle_fill(&mut self) { //Shapes the rule makes no difference to go straight from filled to unfilled
+        let color = PColor(self.color.0 / 2, self.color.1 / 2, self.color.2 / 2, self.color.3);
+        let rules = self.shape.uses_fill_rule();
+        self.fill = match self.fill.take() {
+            None => Some(Fill::solid(color)),
+            Some(Fill { rule: FillRule::EvenOdd, color, paint }) if rules => Some(Fill { color, rule: FillRule::NonZero, paint }),
+            Some(_) => None,
+        };
+    }
+    pub fn cycle_paint(&mut self) { //Solid, then gradients from the fill color to the stroke color across the bounds
+        let ((x0, y0), (x1, y1)) = self.shape.bounds().unwrap_or(((0, 0), (0, 0)));
+        let (x0, y0, x1, y1) = (x0 as f32, y0 as f32, x1 as f32, y1 as f32);
+        let stroke = self.color;
+        if let Some(ref mut fill) = self.fill {
+            let stops = vec![(0., fill.color), (1., stroke)];
+            fill.paint = match fill.paint {
+                Paint::Solid => Paint::Linear { start: (x0, y0), end: (x1, y0), stops, spread: Spread::Pad },
+                Paint::Linear { .. } => Paint::Radial {
+                    center: ((x0 + x1) / 2., (y0 + y1) / 2.),
+                    radius: (x1 - x0).max(y1 - y0) / 2.,
+                    stops,
+                    spread: Spread::Pad,
+                },
+                Paint::Radial { .. } | Paint::Pattern { .. } => Paint::Solid,
+            };
+        }
+    }
+    pub fn set_pattern(&mut self, image: Bitmap, stretch: bool) { //Fills with the image, either tiled at its own size or stretched once over the bounds
+        let ((x0, y0), (x1, y1)) = self.shape.bounds().unwrap_or(((0, 0), (0, 0)));
+        let (w, h) = (image.width().max(1) as f32, image.height().max(1) as f32);
+        let (transform, spread) = if stretch {
+            let t = Transform::translate(x0 as f32, y0 as f32) * Transform::scale((x1 - x0) as f32 / w, (y1 - y0) as f32 / h);
+            (t, Spread::Pad)
+        } else {
+            (Transform::identity(), Spread::Repeat)
+        };
+        let paint = Paint::Pattern { image, transform, spread, filter: Filter::Bilinear };
+        match self.fill {
+            Some(ref mut fill) => fill.paint = paint,
+            None => self.fill = Some(Fill { paint, ..Fill::solid(self.color) }),
+        }
+    }
+    pub fn cycle_composite(&mut self) {
+        self.composite = self.composite.next();
+    }
+    pub fn cycle_blend(&mut self) {
+        self.blend = self.blend.next();
+    }
+    pub fn cycle_pivot(&mut self) {
+        self.pivot = match self.pivot {
+            Pivot::Origin => Pivot::Center,
+            _ => Pivot::Origin,
+        };
+    }
+    pub fn is_opaque(&self) -> bool { //Whether every pixel it draws simply replaces what's below
+        let fill = match self.fill {
+            Some(ref fill) => fill.color.is_opaque() && fill.paint.is_opaque(),
+            None => true,
+        };
+        self.opacity >= 1. && self.composite == Composite::SourceOver && self.blend == Blend::Normal && self.color.is_opaque() && fill && self.shape.is_opaque()
+    }
+    pub fn pivot_point(&self) -> (f32, f32) {
+        match self.pivot {
+            Pivot::Origin => (0., 0.),
+            Pivot::Center => match self.shape.bounds() {
+                Some((p0, p1)) => ((p0.0 + p1.0) as f32 / 2., (p0.1 + p1.1) as f32 / 2.),
+                None => (0., 0.),
+            },
+            Pivot::Point(x, y) => (x, y),
+        }
+    }
+    pub fn transform(&self) -> Transform { //Object space to parent space
+        let linear = Transform::rotate(self.rotation) *
+            Transform::shear(self.shear.0, self.shear.1) *
+            Transform::scale(self.scale.0, self.scale.1);
+        Transform::translate(self.position.0 as f32, self.position.1 as f32) * linear.about(self.pivot_point())
+    }
+    pub fn misses(&self, parent: &Transform, clip: &Clip) -> bool { //Whether its bounds, widened by the stroke, lie wholly outside clip
+        if let Shape::Group(_) = self.shape {
+            return false; //Members are checked one by one, as each has its own stroke
+        }
+        let ((x0, y0), (x1, y1)) = match self.shape.bounds() {
+            Some(bounds) => bounds,
+            None => return true,
+        };
+        let t = *parent * self.transform();
+        let corners: Vec<(f32, f32)> = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)].iter().map(|&(x, y)| t.apply((x as f32, y as f32))).collect();
+        let (min, max) = clip_bounds(clip, self.stroke.reach() + 1.); //A pixel more for antialiasing
+        corners.iter().all(|c| c.0 < min.0) || corners.iter().all(|c| c.0 > max.0) ||
+            corners.iter().all(|c| c.1 < min.1) || corners.iter().all(|c| c.1 > max.1)
+    }
+    pub fn draw(&self, parent: &Transform, clip: &Clip) -> Vec<Point> { //Outline pixels, with lines cut to clip before they are rasterized
+        let t = *parent * self.transform();
+        if !self.stroke.is_thick() && !self.stroke.is_dashed() {
+            return self.shape.draw(&t, clip);
+        }
+        if let Shape::Group(_) = self.shape {
+            return self.shape.draw(&t, clip); //Members are stroked with their own style
+        }
+        let mut points = Vec::new();
+        for (outline, closed) in self.shape.outlines(&t) {
+            points.extend(self.stroke.draw(&outline, closed, clip));
+        }
         points
     }
+    pub fn draw_aa(&self, parent: &Transform, clip: &Clip) -> Vec<(Point, f32)> { //Stroke with per-pixel coverage, one entry per pixel
+        let t = *parent * self.transform();
+        let raw = match self.shape {
+            Shape::Group(_) => self.shape.draw_aa(&t, clip), //Members are stroked with their own style
+            _ if self.stroke.is_thick() => {
+                let mut vec = Vec::new();
+                for (outline, closed) in self.shape.outlines(&t) {
+                    vec.extend(self.stroke.draw_aa(&outline, closed, clip));
+                }
+                vec
+            },
+            _ if self.stroke.is_dashed() => {
+                let mut vec = Vec::new();
+                for (outline, closed) in self.shape.outlines(&t) {
+                    for piece in self.stroke.dashes(&outline, closed, clip) {
+                        vec.extend(polyline_aa(&piece, clip));
+                    }
+                }
+                vec
+            },
+            _ => self.shape.draw_aa(&t, clip),
+        };
+        let mut coverage: HashMap<Point, f32> = HashMap::new();
+        for (p, c) in raw {
+            let e = coverage.entry(p).or_insert(0.);
+            *e = e.max(c);
+        }
+        coverage.into_iter().collect()
+    }
+    pub fn draw_shaded(&self, parent: &Transform, clip: &Clip) -> Vec<(Point, PColor)> { //Pixels that carry their own color, drawn between fill and stroke
+        self.shape.shade(&(*parent * self.transform()), clip)
+    }
+    pub fn draw_paint(&self, parent: &Transform, clip: &Clip) -> Vec<(Point, PColor)> { //Fill pixels colored by the paint at their centers
+        let fill = match self.fill {
+            Some(ref fill) => fill,
+            None => return Vec::new(),
+        };
+        let t = *parent * self.transform();
+        let inverse = match t.invert() {
+            Some(inverse) => inverse,
+            None => return Vec::new(), //Squashed flat, so nothing to fill
+        };
+        let pixels = self.draw_fill(parent, clip);
+        let local: Vec<(f32, f32)> = pixels.iter().map(|p| inverse.apply((p.0 as f32 + 0.5, p.1 as f32 + 0.5))).collect();
+        pixels.into_iter().zip(fill.paint.colors_at(&local, fill.color)).collect()
+    }
+    pub fn draw_fill(&self, parent: &Transform, clip: &Clip) -> Vec<Point> {
+        match self.fill {
+            Some(ref fill) => self.shape.fill(fill.rule, &(*parent * self.transform()), clip),
+            None => Vec::new(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum Shape {
-    Circle { width: i32, height: i32 },
+    Circle {
+        width: i32,
+        height: i32,
+        #[serde(default)]
+        rotation: Angle, //Of the axes, before the object's own transform
+    },
+    Arc {
+        width: i32,
+        height: i32,
+        start: Angle, //Clockwise on screen from the positive x axis
+        end: Angle, //Less than start to run counterclockwise
+        #[serde(default)]
+        kind: ArcKind,
+    },
     Rect(Point, Point),
+    RoundRect(Point, Point, [i32; 4]), //Corner radii clockwise from top-left
     Polygon(Vec<Point>),
     Letters(String),
     Lines(Vec<Line>),
+    Triangles(Vec<Vertex>), //Every three vertices make a triangle, colored by interpolating between them
+    Bezier {
+        start: Point,
+        curves: Vec<Curve>,
+        #[serde(default = "default_tolerance")]
+        tolerance: f32, //Furthest the flattened outline may stray from the true curve, in screen pixels
+    },
+    Path {
+        commands: Vec<PathCommand>,
+        #[serde(default = "default_tolerance")]
+        tolerance: f32,
+    },
+    Image {
+        image: Bitmap, //Top-left at the origin, one unit per pixel
+        #[serde(default)]
+        filter: Filter,
+    },
     Group(ObjectList),
 }
 
 impl Shape {
-    fn draw(&self, scale: (f32, f32)) -> Vec<Point> {
+    pub fn draw(&self, t: &Transform, clip: &Clip) -> Vec<Point> { //Geometry is transformed first, then rasterized in screen space
         match self {
-            Shape::Circle { width, height } => ellipse( (0, 0), *width, *height),
-            Shape::Rect(p0, p1) => rect(*p0, *p1),
-            Shape::Polygon(points) => polygon(points),
+            Shape::Circle { width, height, rotation } => {
+                let t = *t * Transform::rotate(*rotation);
+                if t.is_axis_aligned() {
+                    let (center, a, b) = aligned_ellipse(*width, *height, &t);
+                    ellipse(center, a, b, clip)
+                } else {
+                    let (center, u, v) = ellipse_axes(*width, *height, &t);
+                    conic_ellipse(center, u, v, clip)
+                }
+            },
+            Shape::Arc { width, height, start, end, kind } if t.is_axis_aligned() => aligned_arc(*width, *height, *start, *end, *kind, t, clip),
+            Shape::Arc { width, height, start, end, kind } => {
+                let (points, closed) = arc_outline(*width, *height, *start, *end, *kind, t);
+                polyline(&points, closed, clip)
+            },
+            Shape::Rect(p0, p1) => polyline(&rect_outline(*p0, *p1, t), true, clip), //Corners kept subpixel until clipped, in case they are far outside
+            Shape::RoundRect(p0, p1, radii) if t.is_axis_aligned() => {
+                let (p0, p1, radii) = aligned_round_rect(*p0, *p1, *radii, t);
+                round_rect(p0, p1, radii, clip)
+            },
+            Shape::RoundRect(p0, p1, radii) => polyline(&round_rect_outline(*p0, *p1, *radii, t), true, clip),
+            Shape::Polygon(points) => {
+                let corners: Vec<_> = points.iter().map(|p| t.apply((p.0 as f32, p.1 as f32))).collect();
+                polyline(&corners, true, clip)
+            },
             Shape::Letters(s) => {
                 let mut vec = Vec::new();
                 for (i, ch) in s.chars().enumerate() {
-                    let mut points = Shape::for_letter(ch).draw(scale);
-                    points.translate((100 * i as i32)%1100, 100 * ((100 * i as i32)/1100));
-                    vec.extend(points.into_iter());
+                    let (x, y) = letter_offset(i);
+                    let offset = Transform::translate(x as f32, y as f32);
+                    vec.extend(Shape::for_letter(ch).draw(&(*t * offset), clip).into_iter());
                 }
                 vec
             }
             Shape::Lines(lines) => {
                 let mut vec = Vec::new();
                 for l in lines {
-                    vec.extend(line(l.0, l.1));
+                    let ends = [t.apply(((l.0).0 as f32, (l.0).1 as f32)), t.apply(((l.1).0 as f32, (l.1).1 as f32))];
+                    vec.extend(polyline(&ends, false, clip));
+                }
+                vec
+            },
+            Shape::Bezier { start, curves, tolerance } => polyline(&bezier::flatten(*start, curves, *tolerance, t), false, clip),
+            Shape::Triangles(_) | Shape::Image { .. } => Vec::new(), //Shaded rather than outlined
+            Shape::Path { commands, tolerance } => {
+                let mut vec = Vec::new();
+                for (points, closed) in path::flatten(commands, *tolerance, t) {
+                    vec.extend(polyline(&points, closed, clip));
                 }
                 vec
             },
             Shape::Group(objs) => {
                 let mut vec = Vec::new();
-                for o in objs.0.iter() {
-                    vec.extend(o.borrow().draw().into_iter());
+                for o in objs.0.iter().map(|o| o.borrow()).filter(|o| !o.misses(t, clip)) {
+                    vec.extend(o.draw_fill(t, clip).into_iter());
+                    vec.extend(o.draw(t, clip).into_iter());
+                }
+                vec
+            }
+        }
+    }
+    pub fn draw_aa(&self, t: &Transform, clip: &Clip) -> Vec<(Point, f32)> {
+        match self {
+            Shape::Circle { width, height, rotation } => polygon_aa(&ellipse_outline(*width, *height, &(*t * Transform::rotate(*rotation))), clip),
+            Shape::Arc { width, height, start, end, kind } => match arc_outline(*width, *height, *start, *end, *kind, t) {
+                (points, true) => polygon_aa(&points, clip),
+                (points, false) => polyline_aa(&points, clip),
+            },
+            Shape::Rect(p0, p1) => polygon_aa(&rect_outline(*p0, *p1, t), clip),
+            Shape::RoundRect(p0, p1, radii) => polygon_aa(&round_rect_outline(*p0, *p1, *radii, t), clip),
+            Shape::Polygon(points) => {
+                let corners: Vec<_> = points.iter().map(|p| t.apply((p.0 as f32, p.1 as f32))).collect();
+                polygon_aa(&corners, clip)
+            },
+            Shape::Letters(s) => {
+                let mut vec = Vec::new();
+                for (i, ch) in s.chars().enumerate() {
+                    let (x, y) = letter_offset(i);
+                    let offset = Transform::translate(x as f32, y as f32);
+                    vec.extend(Shape::for_letter(ch).draw_aa(&(*t * offset), clip).into_iter());
+                }
+                vec
+            }
+            Shape::Lines(lines) => {
+                let mut vec = Vec::new();
+                for l in lines {
+                    let p0 = t.apply(((l.0).0 as f32, (l.0).1 as f32));
+                    let p1 = t.apply(((l.1).0 as f32, (l.1).1 as f32));
+                    vec.extend(line_aa(p0, p1, clip));
+                }
+                vec
+            },
+            Shape::Bezier { start, curves, tolerance } => polyline_aa(&bezier::flatten(*start, curves, *tolerance, t), clip),
+            Shape::Triangles(_) | Shape::Image { .. } => Vec::new(),
+            Shape::Path { commands, tolerance } => {
+                let mut vec = Vec::new();
+                for (points, closed) in path::flatten(commands, *tolerance, t) {
+                    vec.extend(if closed { polygon_aa(&points, clip) } else { polyline_aa(&points, clip) });
+                }
+                vec
+            },
+            Shape::Group(objs) => {
+                let mut vec = Vec::new();
+                for o in objs.0.iter().map(|o| o.borrow()).filter(|o| !o.misses(t, clip)) {
+                    vec.extend(o.draw_fill(t, clip).into_iter().map(|p| (p, 1.)));
+                    vec.extend(o.draw_aa(t, clip).into_iter());
                 }
                 vec
             }
         }
     }
-    fn for_letter(c: char) -> Shape {
+    pub fn outlines(&self, t: &Transform) -> Vec<(Vec<(f32, f32)>, bool)> { //Screen space polylines and whether each is closed
+        let apply = |p: &Point| t.apply((p.0 as f32, p.1 as f32));
+        match self {
+            Shape::Circle { width, height, rotation } => vec![(ellipse_outline(*width, *height, &(*t * Transform::rotate(*rotation))), true)],
+            Shape::Arc { width, height, start, end, kind } => vec![arc_outline(*width, *height, *start, *end, *kind, t)],
+            Shape::Rect(p0, p1) => vec![(rect_outline(*p0, *p1, t), true)],
+            Shape::RoundRect(p0, p1, radii) => vec![(round_rect_outline(*p0, *p1, *radii, t), true)],
+            Shape::Polygon(points) => vec![(points.iter().map(apply).collect(), true)],
+            Shape::Letters(s) => {
+                let mut vec = Vec::new();
+                for (i, ch) in s.chars().enumerate() {
+                    let (x, y) = letter_offset(i);
+                    let offset = Transform::translate(x as f32, y as f32);
+                    vec.extend(Shape::for_letter(ch).outlines(&(*t * offset)));
+                }
+                vec
+            },
+            Shape::Lines(lines) => {
+                //Lines that pick up where the previous one ended form one polyline, so dashes and joins carry over
+                let mut vec: Vec<(Vec<(f32, f32)>, bool)> = Vec::new();
+                let mut last: Option<Point> = None;
+                for l in lines {
+                    match vec.last_mut() {
+                        Some(&mut (ref mut polyline, _)) if last == Some(l.0) => polyline.push(apply(&l.1)),
+                        _ => vec.push((vec![apply(&l.0), apply(&l.1)], false)),
+                    }
+                    last = Some(l.1);
+                }
+                vec
+            },
+            Shape::Bezier { start, curves, tolerance } => vec![(bezier::flatten(*start, curves, *tolerance, t), false)],
+            Shape::Path { commands, tolerance } => path::flatten(commands, *tolerance, t),
+            Shape::Triangles(_) | Shape::Image { .. } | Shape::Group(_) => Vec::new(),
+        }
+    }
+    pub fn fill(&self, rule: FillRule, t: &Transform, clip: &Clip) -> Vec<Point> { //Rows and spans limited to clip
+        match self {
+            Shape::Circle { width, height, rotation } => { //Convex, so the winding rule makes no difference
+                let t = *t * Transform::rotate(*rotation);
+                if t.is_axis_aligned() {
+                    let (center, a, b) = aligned_ellipse(*width, *height, &t);
+                    fill_ellipse(center, a, b, clip)
+                } else {
+                    let (center, u, v) = ellipse_axes(*width, *height, &t);
+                    fill_conic_ellipse(center, u, v, clip)
+                }
+            },
+            Shape::Arc { width, height, start, end, kind } => fill_polygon_f(&arc_outline(*width, *height, *start, *end, *kind, t).0, rule, clip),
+            Shape::Rect(p0, p1) if t.is_axis_aligned() => fill_rect(t.apply_point(*p0), t.apply_point(*p1), clip),
+            Shape::Rect(p0, p1) => fill_polygon(&rect_corners(*p0, *p1, t), rule, clip),
+            Shape::RoundRect(p0, p1, radii) if t.is_axis_aligned() => {
+                let (p0, p1, radii) = aligned_round_rect(*p0, *p1, *radii, t);
+                fill_round_rect(p0, p1, radii, clip)
+            },
+            Shape::RoundRect(p0, p1, radii) => fill_polygon_f(&round_rect_outline(*p0, *p1, *radii, t), rule, clip),
+            Shape::Polygon(points) => {
+                let mut corners = points.clone();
+                corners.transform(t);
+                fill_polygon(&corners, rule, clip)
+            },
+            Shape::Bezier { start, curves, tolerance } => fill_polygon_f(&bezier::flatten(*start, curves, *tolerance, t), rule, clip), //Closed by a straight edge back to the start
+            Shape::Triangles(vertices) => { //Flat fill over the whole mesh
+                let mut vec = Vec::new();
+                for (tri, _) in screen_triangles(vertices, t) {
+                    vec.extend(triangle(tri, clip).into_iter().map(|(p, _)| p));
+                }
+                vec
+            },
+            Shape::Path { commands, tolerance } => {
+                //Every subpath is filled as if closed, all in one pass so holes follow the rule
+                let loops: Vec<_> = path::flatten(commands, *tolerance, t).into_iter().map(|(points, _)| points).collect();
+                fill_loops_f(&loops, rule, clip)
+            },
+            Shape::Image { image, .. } => Shape::Rect((0, 0), image_size(image)).fill(rule, t, clip), //Backdrop showing through transparent pixels
+            _ => Vec::new(),
+        }
+    }
+    pub fn uses_fill_rule(&self) -> bool { //Whether its outline can cross itself or hold holes, so even-odd and nonzero fill differently
+        matches!(self, Shape::Polygon(_) | Shape::Bezier { .. } | Shape::Path { .. })
+    }
+    pub fn is_opaque(&self) -> bool { //Colors the shape brings itself, rather than the object's
+        match self {
+            Shape::Triangles(vertices) => vertices.iter().all(|v| v.1.is_opaque()),
+            Shape::Image { image, .. } => image.is_opaque(),
+            Shape::Group(objs) => objs.0.iter().all(|o| o.borrow().shape.is_opaque()),
+            _ => true,
+        }
+    }
+    pub fn shade(&self, t: &Transform, clip: &Clip) -> Vec<(Point, PColor)> {
+        match self {
+            Shape::Triangles(vertices) => {
+                let mut vec = Vec::new();
+                for (tri, colors) in screen_triangles(vertices, t) {
+                    for (p, w) in triangle(tri, clip) {
+                        vec.push((p, PColor::mix(&[(colors[0], w[0]), (colors[1], w[1]), (colors[2], w[2])])));
+                    }
+                }
+                vec
+            },
+            Shape::Image { image, filter } => image_pixels(image, *filter, t, clip),
+            Shape::Group(objs) => {
+                let mut vec = Vec::new();
+                for o in objs.0.iter().map(|o| o.borrow()).filter(|o| !o.misses(t, clip)) {
+                    vec.extend(o.draw_shaded(t, clip));
+                }
+                vec
+            },
+            _ => Vec::new(),
+        }
+    }
+    pub fn bounds(&self) -> Option<(Point, Point)> { //Top-left and bottom-right of the untransformed geometry
+        match self {
+            Shape::Circle { width, height, rotation } => {
+                let (sin, cos) = rotation.to_radians().sin_cos();
+                let (w, h) = (*width as f32, *height as f32);
+                let x = (w * cos).hypot(h * sin).ceil() as i32;
+                let y = (w * sin).hypot(h * cos).ceil() as i32;
+                Some(((-x, -y), (x, y)))
+            },
+            Shape::Arc { width, height, .. } => Some(((-width, -height), (*width, *height))), //Whole ellipse, so a center pivot is the center of the dial
+            Shape::Rect(p0, p1) => bounds_of(&[*p0, *p1]),
+            Shape::RoundRect(p0, p1, _) => bounds_of(&[*p0, *p1]),
+            Shape::Polygon(points) => bounds_of(points),
+            Shape::Letters(s) => {
+                let mut corners = Vec::new();
+                for (i, ch) in s.chars().enumerate() {
+                    if let Some((p0, p1)) = Shape::for_letter(ch).bounds() {
+                        let (x, y) = letter_offset(i);
+                        let mut c = vec![p0, p1];
+                        c.translate(x, y);
+                        corners.extend(c);
+                    }
+                }
+                bounds_of(&corners)
+            },
+            Shape::Lines(lines) => {
+                let ends: Vec<Point> = lines.iter().flat_map(|l| vec![l.0, l.1]).collect();
+                bounds_of(&ends)
+            },
+            Shape::Bezier { start, curves, .. } => bounds_of(&bezier::control_points(*start, curves)),
+            Shape::Triangles(vertices) => bounds_of(&vertices.iter().map(|v| v.0).collect::<Vec<_>>()),
+            Shape::Image { image, .. } => Some(((0, 0), image_size(image))),
+            Shape::Path { commands, tolerance } => {
+                //Arcs can bulge past their end points, so measure the flattened outline
+                let points: Vec<Point> = path::flatten(commands, *tolerance, &Transform::identity()).into_iter()
+                    .flat_map(|(points, _)| points)
+                    .flat_map(|p| vec![(p.0.floor() as i32, p.1.floor() as i32), (p.0.ceil() as i32, p.1.ceil() as i32)])
+                    .collect();
+                bounds_of(&points)
+            },
+            Shape::Group(objs) => {
+                let mut corners = Vec::new();
+                for o in objs.0.iter() {
+                    let o = o.borrow();
+                    if let Some((p0, p1)) = o.shape.bounds() {
+                        let mut c = vec![p0, (p1.0, p0.1), p1, (p0.0, p1.1)];
+                        c.transform(&o.transform());
+                        corners.extend(c);
+                    }
+                }
+                bounds_of(&corners)
+            },
+        }
+    }
+    pub fn for_letter(c: char) -> Shape {
         let s = 50;
         let c = c.to_uppercase().next().unwrap();
         let vec = match c {
@@ -349,3 +953,490 @@ impl Shape {
         Shape::Lines(vec)
     }
 }
+
+fn aligned_ellipse(width: i32, height: i32, t: &Transform) -> (Point, i32, i32) { //Center and radii after an axis-aligned transform
+    let a = (width as f32 * t.a.abs()).round() as i32;
+    let b = (height as f32 * t.d.abs()).round() as i32;
+    (t.apply_point((0, 0)), a, b)
+}
+
+fn aligned_arc(width: i32, height: i32, start: Angle, end: Angle, kind: ArcKind, t: &Transform, clip: &Clip) -> Vec<Point> { //Midpoint pixels within the angle range
+    let (center, a, b) = aligned_ellipse(width, height, t);
+    //A mirrored axis mirrors the angles, and flips the direction if only one axis is mirrored
+    let (sx, sy) = (t.a.signum(), t.d.signum());
+    let theta = start.to_radians();
+    let phi = (sy * theta.sin()).atan2(sx * theta.cos());
+    let sweep = (end - start).to_radians() * sx * sy;
+    let mut points = ellipse_arc(center, a, b, phi, sweep, clip);
+    let at = |phi: f32| (center.0.saturating_add((a as f32 * phi.cos()).round() as i32), center.1.saturating_add((b as f32 * phi.sin()).round() as i32));
+    let (p0, p1) = (at(phi), at(phi + sweep));
+    match kind {
+        ArcKind::Open => {},
+        ArcKind::Pie => {
+            points.extend(line(center, p0, clip));
+            points.extend(line(center, p1, clip));
+        },
+        ArcKind::Chord => points.extend(line(p0, p1, clip)),
+    }
+    points
+}
+
+fn arc_outline(width: i32, height: i32, start: Angle, end: Angle, kind: ArcKind, t: &Transform) -> (Vec<(f32, f32)>, bool) { //Flattened arc and whether it is closed
+    let two_pi = 2. * ::std::f32::consts::PI;
+    let sweep = (end - start).to_radians().clamp(-two_pi, two_pi);
+    let cubics = arc_cubics((0., 0.), (width as f32, height as f32), Angle::default(), start.to_radians(), sweep);
+    let mut points = vec![t.apply(cubics[0][0])];
+    for c in cubics {
+        let p0 = points[points.len() - 1];
+        points.extend(flatten_cubic(p0, t.apply(c[1]), t.apply(c[2]), t.apply(c[3]), default_tolerance()));
+    }
+    match kind {
+        ArcKind::Open => (points, false),
+        ArcKind::Pie => {
+            points.push(t.apply((0., 0.)));
+            (points, true)
+        },
+        ArcKind::Chord => (points, true),
+    }
+}
+
+fn ellipse_axes(width: i32, height: i32, t: &Transform) -> ((f32, f32), (f32, f32), (f32, f32)) { //Center and conjugate semi-axes after any transform
+    let o = t.apply((0., 0.));
+    let u = t.apply((width as f32, 0.));
+    let v = t.apply((0., height as f32));
+    (o, (u.0 - o.0, u.1 - o.1), (v.0 - o.0, v.1 - o.1))
+}
+
+fn ellipse_outline(width: i32, height: i32, t: &Transform) -> Vec<(f32, f32)> { //Ellipse flattened to a polygon with edges a few pixels long
+    let rx = t.apply((width as f32, 0.));
+    let ry = t.apply((0., height as f32));
+    let o = t.apply((0., 0.));
+    let r = ((rx.0 - o.0).hypot(rx.1 - o.1)).max((ry.0 - o.0).hypot(ry.1 - o.1));
+    let n = ((2. * ::std::f32::consts::PI * r / 4.).ceil() as usize).max(8);
+    (0..n).map(|i| {
+        let theta = 2. * ::std::f32::consts::PI * i as f32 / n as f32;
+        t.apply((width as f32 * theta.cos(), height as f32 * theta.sin()))
+    }).collect()
+}
+
+fn rect_corners(p0: Point, p1: Point, t: &Transform) -> Vec<Point> {
+    let mut corners = vec![p0, (p1.0, p0.1), p1, (p0.0, p1.1)];
+    corners.transform(t);
+    corners
+}
+
+fn rect_outline(p0: Point, p1: Point, t: &Transform) -> Vec<(f32, f32)> {
+    vec![p0, (p1.0, p0.1), p1, (p0.0, p1.1)].into_iter().map(|p| t.apply((p.0 as f32, p.1 as f32))).collect()
+}
+
+fn corner_radii(p0: Point, p1: Point, radii: [i32; 4]) -> [f32; 4] { //Shrunk together until neighbouring corners fit along each side, as CSS does
+    let (w, h) = ((p1.0 - p0.0).abs() as f32, (p1.1 - p0.1).abs() as f32);
+    let r: Vec<f32> = radii.iter().map(|&r| r.max(0) as f32).collect();
+    let mut k: f32 = 1.;
+    for &(a, b, side) in &[(r[0], r[1], w), (r[3], r[2], w), (r[0], r[3], h), (r[1], r[2], h)] {
+        if a + b > side {
+            k = k.min(side / (a + b));
+        }
+    }
+    [r[0] * k, r[1] * k, r[2] * k, r[3] * k]
+}
+
+fn aligned_round_rect(p0: Point, p1: Point, radii: [i32; 4], t: &Transform) -> (Point, Point, [(f32, f32); 4]) { //Screen corners and radii after an axis-aligned transform
+    let r = corner_radii(p0, p1, radii);
+    let (sx, sy) = (t.a.abs(), t.d.abs());
+    let mut radii = [(r[0] * sx, r[0] * sy), (r[1] * sx, r[1] * sy), (r[2] * sx, r[2] * sy), (r[3] * sx, r[3] * sy)];
+    //Corners are numbered from the top-left on screen, which moves when an axis is mirrored
+    let (x0, x1) = (p0.0.min(p1.0), p0.0.max(p1.0));
+    let (y0, y1) = (p0.1.min(p1.1), p0.1.max(p1.1));
+    if t.a < 0. {
+        radii = [radii[1], radii[0], radii[3], radii[2]];
+    }
+    if t.d < 0. {
+        radii = [radii[3], radii[2], radii[1], radii[0]];
+    }
+    (t.apply_point((x0, y0)), t.apply_point((x1, y1)), radii)
+}
+
+fn round_rect_outline(p0: Point, p1: Point, radii: [i32; 4], t: &Transform) -> Vec<(f32, f32)> { //Straight sides joined by flattened quarter ellipses
+    let r = corner_radii(p0, p1, radii);
+    let (x0, x1) = (p0.0.min(p1.0) as f32, p0.0.max(p1.0) as f32);
+    let (y0, y1) = (p0.1.min(p1.1) as f32, p0.1.max(p1.1) as f32);
+    let half_pi = ::std::f32::consts::FRAC_PI_2;
+    let corners = [
+        ((x0 + r[0], y0 + r[0]), r[0], 2.),
+        ((x1 - r[1], y0 + r[1]), r[1], 3.),
+        ((x1 - r[2], y1 - r[2]), r[2], 0.),
+        ((x0 + r[3], y1 - r[3]), r[3], 1.),
+    ];
+    let mut points: Vec<(f32, f32)> = Vec::new();
+    for &(center, radius, quarter) in &corners {
+        let cubics = arc_cubics(center, (radius, radius), Angle::default(), quarter * half_pi, half_pi);
+        points.push(t.apply(cubics[0][0]));
+        if radius > 0. {
+            for c in cubics {
+                let p0 = points[points.len() - 1];
+                points.extend(flatten_cubic(p0, t.apply(c[1]), t.apply(c[2]), t.apply(c[3]), default_tolerance()));
+            }
+        }
+    }
+    points
+}
+
+type ScreenTriangle = ([(f32, f32); 3], [PColor; 3]); //Corners on screen and their vertex colors
+
+fn screen_triangles(vertices: &[Vertex], t: &Transform) -> Vec<ScreenTriangle> { //Leftover vertices that don't make a whole triangle are ignored
+    vertices.chunks(3).filter(|c| c.len() == 3).map(|c| {
+        let apply = |v: &Vertex| t.apply(((v.0).0 as f32, (v.0).1 as f32));
+        ([apply(&c[0]), apply(&c[1]), apply(&c[2])], [c[0].1, c[1].1, c[2].1])
+    }).collect()
+}
+
+fn letter_offset(i: usize) -> Point { //Top-left of the i-th glyph in a string, wrapping at 11 per row
+    let i = i as i32;
+    ((100 * i)%1100, 100 * ((100 * i)/1100))
+}
+
+fn image_size(image: &Bitmap) -> Point {
+    (image.width() as i32, image.height() as i32)
+}
+
+fn image_pixels(image: &Bitmap, filter: Filter, t: &Transform, clip: &Clip) -> Vec<(Point, PColor)> { //Screen pixels in clip whose centers map back inside the image, sampled there
+    let inverse = match t.invert() {
+        Some(inverse) => inverse,
+        None => return Vec::new(),
+    };
+    let (w, h) = (image.width() as f32, image.height() as f32);
+    let corners: Vec<_> = [(0., 0.), (w, 0.), (w, h), (0., h)].iter().map(|&c| t.apply(c)).collect();
+    let x0 = corners.iter().fold(f32::INFINITY, |m, c| m.min(c.0)).floor() as i32;
+    let x1 = corners.iter().fold(f32::NEG_INFINITY, |m, c| m.max(c.0)).ceil() as i32;
+    let y0 = corners.iter().fold(f32::INFINITY, |m, c| m.min(c.1)).floor() as i32;
+    let y1 = corners.iter().fold(f32::NEG_INFINITY, |m, c| m.max(c.1)).ceil() as i32;
+    let ((cx0, cy0), (cx1, cy1)) = *clip;
+    let (x0, x1) = (x0.max(cx0), x1.min(cx1.saturating_add(1)));
+    let (y0, y1) = (y0.max(cy0), y1.min(cy1.saturating_add(1)));
+    let mut vec = Vec::new();
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let p = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+            if p.0 >= 0. && p.1 >= 0. && p.0 < w && p.1 < h { //Half-open, so images laid edge to edge don't overlap
+                vec.push(((x, y), image.sample(p, filter, Spread::Pad)));
+            }
+        }
+    }
+    vec
+}
+
+fn bounds_of(points: &[Point]) -> Option<(Point, Point)> {
+    if points.is_empty() {
+        return None;
+    }
+    let x0 = points.iter().map(|p| p.0).min().unwrap();
+    let y0 = points.iter().map(|p| p.1).min().unwrap();
+    let x1 = points.iter().map(|p| p.0).max().unwrap();
+    let y1 = points.iter().map(|p| p.1).max().unwrap();
+    Some(((x0, y0), (x1, y1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use backend::{Op, Recorder};
+    use bitmap::{read_png, read_ppm};
+    use util::{clip_contains, UNCLIPPED};
+
+    #[test]
+    fn full_turn_draws_original() {
+        let shapes = vec![
+            Shape::Polygon(vec![(0, 0), (120, 10), (60, 90)]),
+            Shape::Rect((-20, -10), (40, 30)),
+            Shape::Circle { width: 50, height: 20, rotation: Angle::default() },
+            Shape::Letters("AB".to_string()),
+        ];
+        for shape in shapes {
+            let o = Object::new(shape, (300, 200));
+            for &pivot in &[Pivot::Origin, Pivot::Center, Pivot::Point(-40., 75.)] {
+                let mut o = o.borrow_mut();
+                o.pivot = pivot;
+                o.rotation = Angle::degrees(0.);
+                let mut before = o.draw(&Transform::identity(), &UNCLIPPED);
+                o.rotation = Angle::degrees(360.);
+                let mut after = o.draw(&Transform::identity(), &UNCLIPPED);
+                before.sort();
+                after.sort();
+                assert_eq!(after, before);
+            }
+        }
+    }
+
+    #[test]
+    fn translucent_object_blends_once() {
+        //Stroke over fill inside the object is settled first, then the whole object is blended at half opacity
+        let mut scene = Scene::new();
+        let o = Object::with_color(Shape::Rect((10, 10), (50, 50)), (0, 0), (200, 100, 0));
+        o.borrow_mut().fill = Some(Fill::solid(PColor::rgb(200, 100, 0)));
+        o.borrow_mut().opacity = 0.5;
+        scene.objects.push(o);
+        let fb = scene.render(VIEW_SIZE.0, VIEW_SIZE.1).unwrap();
+        assert_eq!(fb.get_pixel((10, 30)), Some((100, 50, 0, 255)));
+        assert_eq!(fb.get_pixel((30, 30)), Some((100, 50, 0, 255)));
+        assert_eq!(fb.get_pixel((60, 30)), Some((0, 0, 0, 255)));
+    }
+
+    #[test]
+    fn destination_in_clears_outside_the_object() {
+        let mut scene = Scene::new();
+        let under = Object::new(Shape::Rect((0, 0), (80, 40)), (0, 0));
+        under.borrow_mut().fill = Some(Fill::solid(PColor::rgb(0, 0, 255)));
+        scene.objects.push(under);
+        let mask = Object::new(Shape::Rect((10, 10), (30, 30)), (0, 0));
+        mask.borrow_mut().fill = Some(Fill::solid(PColor::rgb(255, 255, 255)));
+        mask.borrow_mut().composite = Composite::DestinationIn;
+        scene.objects.push(mask);
+        let mut fb = Framebuffer::new(VIEW_SIZE.0, VIEW_SIZE.1).unwrap();
+        scene.draw_view(&mut fb, scene.view(), &viewport(VIEW_SIZE.0, VIEW_SIZE.1), false);
+        assert_eq!(fb.get_pixel((20, 20)), Some((0, 0, 255, 255))); //Kept under the mask
+        assert_eq!(fb.get_pixel((60, 20)), Some((0, 0, 0, 0))); //Destination outside the object is cut away
+        assert_eq!(fb.get_pixel((200, 200)), Some((0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn renders_to_images() {
+        let mut scene = Scene::new();
+        let o = Object::with_color(Shape::Rect((0, 0), (100, 50)), (200, 100), (255, 0, 0));
+        o.borrow_mut().fill = Some(Fill::solid(PColor::rgb(0, 0, 255)));
+        scene.objects.push(o);
+        let fb = scene.render(VIEW_SIZE.0 / 4, VIEW_SIZE.1 / 4).unwrap();
+        //Resampled to a quarter of the view: the rectangle lands at (50, 25)-(75, 37)
+        assert_eq!(fb.get_pixel((50, 30)), Some((255, 0, 0, 255)));
+        assert_eq!(fb.get_pixel((60, 30)), Some((0, 0, 255, 255)));
+        assert_eq!(fb.get_pixel((10, 10)), Some((0, 0, 0, 255)));
+        let expected: Vec<PColor> = fb.pixels().chunks(4).map(|px| PColor(px[0], px[1], px[2], px[3])).collect();
+        let mut ppm = Vec::new();
+        fb.write_ppm(&mut ppm).unwrap();
+        assert_eq!(read_ppm(&ppm[..]).unwrap(), (fb.width(), fb.height(), expected.clone()));
+        let mut png = Vec::new();
+        fb.write_png(&mut png).unwrap();
+        assert_eq!(read_png(io::Cursor::new(png)).unwrap(), (fb.width(), fb.height(), expected));
+        assert!(scene.render(u32::MAX, u32::MAX).is_none());
+    }
+
+    #[test]
+    fn image_paths_are_relative_to_the_scene() {
+        let dir = ::std::env::temp_dir().join(format!("scene-images-{}", ::std::process::id()));
+        ::std::fs::create_dir_all(&dir).unwrap();
+        ::std::fs::write(dir.join("dot.ppm"), b"P6 1 1 255\n\x01\x02\x03").unwrap();
+        let scene_path = dir.join("scene.json");
+        let image = dir.join("dot.ppm").to_str().unwrap().to_string();
+        let mut scene = Scene::new();
+        scene.objects.push(Object::new(Shape::Image { image: Bitmap::open(&image).unwrap(), filter: Filter::Nearest }, (0, 0)));
+        //Saved as if written next to the image, then loaded from another working directory
+        let json = serde_json::to_string(&scene).unwrap().replace(&serde_json::to_string(&image).unwrap(), "\"dot.ppm\"");
+        ::std::fs::write(&scene_path, json).unwrap();
+        let loaded = Scene::load(&scene_path);
+        ::std::fs::remove_dir_all(&dir).unwrap();
+        let scene = loaded.unwrap();
+        let object = scene.objects[10].borrow();
+        match object.shape {
+            Shape::Image { ref image, .. } => {
+                assert_eq!(image.path(), Some("dot.ppm")); //Saved again as written
+                assert_eq!(image.texel(0, 0, Spread::Pad), PColor::rgb(1, 2, 3));
+            },
+            _ => panic!("expected an image"),
+        }
+    }
+
+    #[test]
+    fn fill_rule_cycles_only_where_it_matters() {
+        let rules = |shape| {
+            let o = Object::new(shape, (0, 0));
+            let mut o = o.borrow_mut();
+            (0..3).map(|_| {
+                o.cycle_fill();
+                o.fill.as_ref().map(|f| f.rule)
+            }).collect::<Vec<_>>()
+        };
+        let convex = rules(Shape::Circle { width: 10, height: 5, rotation: Angle::default() });
+        assert_eq!(convex, vec![Some(FillRule::EvenOdd), None, Some(FillRule::EvenOdd)]);
+        let polygon = rules(Shape::Polygon(vec![(0, 0), (10, 0), (5, 8)]));
+        assert_eq!(polygon, vec![Some(FillRule::EvenOdd), Some(FillRule::NonZero), None]);
+    }
+
+    #[test]
+    fn fills_go_out_as_spans() {
+        let mut scene = Scene::new();
+        let (orange, blue) = ((200, 100, 0, 255), (0, 0, 255, 255));
+        let o = Object::with_color(Shape::Rect((10, 20), (14, 22)), (0, 0), orange);
+        o.borrow_mut().fill = Some(Fill::solid(PColor::from(blue)));
+        scene.objects.push(o);
+        let mut recorder = Recorder::new();
+        scene.draw(&mut recorder);
+        let spans: Vec<_> = recorder.ops.iter().filter_map(|op| match *op {
+            Op::PlotSpan(x0, x1, y, c) => Some((x0, x1, y, c)),
+            _ => None,
+        }).collect();
+        assert_eq!(spans, vec![(10, 14, 20, blue), (10, 14, 21, blue), (10, 14, 22, blue)]);
+        //Then the outline over it, pixel by pixel
+        let outline: Vec<_> = recorder.ops.iter().filter_map(|op| match *op {
+            Op::Plot(p, c) => Some((p, c)),
+            _ => None,
+        }).collect();
+        assert!(outline.iter().all(|&(p, c)| c == orange && (p.0 == 10 || p.0 == 14 || p.1 == 20 || p.1 == 22)));
+        assert_eq!(recorder.plotted().len(), spans.len() * 5 + outline.len());
+    }
+
+    #[test]
+    fn solid_paint_matches_flat_gradient() {
+        //Solid fills skip per-pixel shading, but must land on the same pixels as any paint of one color
+        let blue = PColor::rgb(0, 0, 255);
+        let flat = Paint::Linear { start: (0., 0.), end: (10., 0.), stops: vec![(0., blue), (1., blue)], spread: Spread::Pad };
+        let render = |paint: Paint| {
+            let mut scene = Scene::new();
+            let o = Object::new(Shape::Polygon(vec![(-30, -20), (40, -5), (10, 35)]), (60, 50));
+            o.borrow_mut().rotation = Angle::degrees(23.);
+            o.borrow_mut().fill = Some(Fill { paint, ..Fill::solid(blue) });
+            scene.objects.push(o);
+            scene.render(VIEW_SIZE.0, VIEW_SIZE.1).unwrap()
+        };
+        assert!(render(Paint::Solid).pixels() == render(flat).pixels());
+    }
+
+    #[test]
+    fn zoomed_ellipse_is_drawn() {
+        //The editor's startup ellipse at 20 times: radii of 2000 by 1000 on screen
+        let mut scene = Scene::new();
+        let o = Object::new(Shape::Circle { width: 100, height: 50, rotation: Angle::default() }, (300, 300));
+        o.borrow_mut().fill = Some(Fill::solid(PColor::rgb(0, 0, 255)));
+        scene.objects.push(o);
+        scene.scale = (20., 20.);
+        scene.camera = (345, 300); //Center at (-900, 0) on screen
+        let mut recorder = Recorder::new();
+        scene.draw(&mut recorder);
+        let plotted: Vec<Point> = recorder.plotted().into_iter().map(|(p, _)| p).collect();
+        assert!(plotted.contains(&(1100, 0)) && plotted.contains(&(1000, 0)));
+        assert!(!plotted.contains(&(1101, 0)));
+    }
+
+    #[test]
+    fn drawing_stays_in_view() {
+        let blue = PColor::rgb(0, 0, 255);
+        let filled = |shape, position| {
+            let o = Object::new(shape, position);
+            o.borrow_mut().fill = Some(Fill::solid(blue));
+            o
+        };
+        let draw = |scene: &Scene| {
+            let mut recorder = Recorder::new();
+            scene.draw(&mut recorder);
+            recorder
+        };
+        let mut scene = Scene::new();
+        scene.objects.push(filled(Shape::Rect((0, 0), (10, 10)), (5000, 5000)));
+        assert!(draw(&scene).plotted().is_empty());
+        //Just outside, but its stroke reaches in
+        let near = filled(Shape::Rect((-20, 0), (-5, 10)), (0, 0));
+        near.borrow_mut().stroke.width = 12.;
+        scene.objects.push(near);
+        assert!(!draw(&scene).plotted().is_empty());
+        //Far bigger than the view, on its own and in a group: only the view's pixels go out, each once
+        for &degrees in &[30., 0.] { //Rotated ones are solved by rows, axis-aligned ones too once too big to walk
+            let huge = || filled(Shape::Circle { width: 100000, height: 60000, rotation: Angle::degrees(degrees) }, (600, 600));
+            let mut alone = Scene::new();
+            alone.objects.push(huge());
+            let grouped = Scene::new();
+            grouped.groups[0].borrow_mut().shape = Shape::Group(ObjectList(vec![huge()]));
+            for scene in &[alone, grouped] {
+                let plotted = draw(scene).plotted();
+                assert_eq!(plotted.len(), (VIEW_SIZE.0 * VIEW_SIZE.1) as usize);
+                assert!(plotted.iter().all(|&(p, _)| clip_contains(&viewport(VIEW_SIZE.0, VIEW_SIZE.1), p)));
+            }
+        }
+        //Axis-aligned outlines crossing the view from a corner
+        let mut outlines = Scene::new();
+        outlines.objects.push(Object::new(Shape::Circle { width: 1300, height: 1300, rotation: Angle::default() }, (0, 0)));
+        outlines.objects.push(Object::new(Shape::Arc { width: 1300, height: 1300, start: Angle::degrees(0.), end: Angle::degrees(90.), kind: ArcKind::Pie }, (0, 0)));
+        let plotted: Vec<Point> = draw(&outlines).plotted().into_iter().map(|(p, _)| p).collect();
+        assert!(plotted.iter().all(|&p| clip_contains(&viewport(VIEW_SIZE.0, VIEW_SIZE.1), p)));
+        assert!(plotted.contains(&(1199, 502)) && plotted.contains(&(600, 0))); //On the circle, and on the wedge's first side
+    }
+
+    #[test]
+    fn transformed_fills_have_no_holes() {
+        use std::collections::HashSet;
+        let shapes = vec![Shape::Rect((-15, -10), (15, 10)), Shape::Polygon(vec![(-12, -15), (17, -2), (5, 2), (10, 15), (-15, 7)])];
+        for shape in shapes {
+            let o = Object::new(shape, (50, 50));
+            let mut o = o.borrow_mut();
+            o.fill = Some(Fill::solid(PColor::rgb(0, 0, 255)));
+            for &(degrees, scale) in &[(0., 1.), (7., 1.), (30., 2.5), (45., 1.), (61., 0.7), (89., 1.3), (123., 1.), (200., 2.), (333., 0.45)] {
+                o.rotation = Angle::degrees(degrees);
+                o.scale = (scale, scale);
+                let mut drawn: HashSet<Point> = o.draw_fill(&Transform::identity(), &UNCLIPPED).into_iter().collect();
+                drawn.extend(o.draw(&Transform::identity(), &UNCLIPPED));
+                //Flood the outside from a corner; whatever it can't reach must all be drawn
+                let mut outside: HashSet<Point> = HashSet::new();
+                let mut stack = vec![(0, 0)];
+                while let Some(p) = stack.pop() {
+                    if p.0 < 0 || p.1 < 0 || p.0 > 100 || p.1 > 100 || drawn.contains(&p) || !outside.insert(p) {
+                        continue;
+                    }
+                    stack.extend(vec![(p.0 + 1, p.1), (p.0 - 1, p.1), (p.0, p.1 + 1), (p.0, p.1 - 1)]);
+                }
+                let holes = (0..101).flat_map(|y| (0..101).map(move |x| (x, y))).filter(|p| !outside.contains(p) && !drawn.contains(p)).count();
+                assert_eq!(holes, 0, "{} degrees at {}x", degrees, scale);
+                assert!(drawn.contains(&(50, 50)));
+            }
+        }
+    }
+
+    #[test]
+    fn old_scenes_rotate_in_radians() {
+        let json = r#"{"shape":{"Rect":[[0,0],[100,50]]},"position":[10,10],"scale":[1,1],"rotation":1.5707964,"color":[0,255,255]}"#;
+        let o: Object = serde_json::from_str(json).unwrap();
+        assert_eq!(o.rotation, Angle::degrees(90.));
+        assert_eq!(o.transform().apply_point((100, 0)), (10, 110));
+    }
+
+    #[test]
+    fn center_pivot_keeps_center() {
+        let o = Object::new(Shape::Rect((0, 0), (100, 50)), (10, 10));
+        let mut o = o.borrow_mut();
+        o.pivot = Pivot::Center;
+        o.rotation = Angle::degrees(90.);
+        assert_eq!(o.transform().apply_point((50, 25)), (60, 35));
+    }
+
+    #[test]
+    fn pattern_tiles_or_stretches() {
+        let (red, blue) = (PColor::rgb(255, 0, 0), PColor::rgb(0, 0, 255));
+        let image = Bitmap::try_new(2, 1, vec![red, blue]).unwrap();
+        let colors = |stretch| {
+            let o = Object::new(Shape::Rect((0, 0), (20, 10)), (0, 0));
+            let mut o = o.borrow_mut();
+            o.set_pattern(image.clone(), stretch);
+            o.draw_paint(&Transform::identity(), &UNCLIPPED).into_iter().collect::<HashMap<Point, PColor>>()
+        };
+        let tiled = colors(false);
+        assert_eq!([tiled[&(0, 5)], tiled[&(1, 5)], tiled[&(2, 5)], tiled[&(11, 5)]], [red, blue, red, blue]);
+        let stretched = colors(true);
+        assert_eq!([stretched[&(2, 5)], stretched[&(17, 5)]], [red, blue]);
+        assert_eq!(stretched[&(3, 2)], stretched[&(3, 8)]);
+    }
+
+    #[test]
+    fn image_follows_transform() {
+        //Quarter turn of a 2x1 image doubled in size: 2 wide by 4 tall, the first pixel on top
+        let (red, blue) = (PColor::rgb(255, 0, 0), PColor::rgb(0, 0, 255));
+        let image = Bitmap::try_new(2, 1, vec![red, blue]).unwrap();
+        let o = Object::new(Shape::Image { image, filter: Filter::Nearest }, (10, 10));
+        let mut o = o.borrow_mut();
+        o.scale = (2., 2.);
+        o.rotation = Angle::degrees(90.);
+        let mut pixels = o.draw_shaded(&Transform::identity(), &UNCLIPPED);
+        pixels.sort_by_key(|&((x, y), _)| (y, x));
+        let expected: Vec<_> = (10..14).flat_map(|y| (8..10).map(move |x| ((x, y), if y < 12 { red } else { blue }))).collect();
+        assert_eq!(pixels, expected);
+        let mut clipped = o.draw_shaded(&Transform::identity(), &((9, 11), (20, 12)));
+        clipped.sort_by_key(|&((x, y), _)| (y, x));
+        assert_eq!(clipped, vec![((9, 11), red), ((9, 12), blue)]);
+    }
+}
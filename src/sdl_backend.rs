@@ -0,0 +1,52 @@
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+
+use backend::Surface;
+use composite::{Blend, Composite};
+use framebuffer::{Framebuffer, Rgba};
+use util::Point;
+
+pub struct SdlSurface<'a> { //Draws into a framebuffer and uploads it as a streaming texture on present
+    canvas: Canvas<Window>,
+    texture: Texture<'a>,
+    pub fb: Framebuffer,
+}
+
+impl<'a> SdlSurface<'a> {
+    pub fn new(canvas: Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, width: u32, height: u32) -> SdlSurface<'a> {
+        //ABGR8888 is laid out as R, G, B, A bytes in memory on little-endian targets
+        let format = if cfg!(target_endian = "little") { PixelFormatEnum::ABGR8888 } else { PixelFormatEnum::RGBA8888 };
+        SdlSurface {
+            canvas,
+            texture: texture_creator.create_texture_streaming(format, width, height).unwrap(),
+            fb: Framebuffer::new(width, height).expect("window too large for a framebuffer"),
+        }
+    }
+}
+
+impl<'a> Surface for SdlSurface<'a> {
+    fn set_color(&mut self, color: Rgba) {
+        self.fb.set_color(color);
+    }
+    fn plot(&mut self, p: Point) {
+        self.fb.plot(p);
+    }
+    fn plot_span(&mut self, x0: i32, x1: i32, y: i32) {
+        self.fb.plot_span(x0, x1, y);
+    }
+    fn plot_coverage(&mut self, p: Point, coverage: f32) {
+        self.fb.plot_coverage(p, coverage);
+    }
+    fn composite(&mut self, p: Point, color: Rgba, op: Composite, blend: Blend) {
+        self.fb.composite_pixel(p, color, op, blend);
+    }
+    fn clear(&mut self, color: Rgba) {
+        Surface::clear(&mut self.fb, color);
+    }
+    fn present(&mut self) {
+        self.texture.update(None, self.fb.pixels(), self.fb.pitch()).unwrap();
+        self.canvas.copy(&self.texture, None, None).unwrap();
+        self.canvas.present();
+    }
+}
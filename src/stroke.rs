@@ -0,0 +1,381 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use util::{clip_polyline, fill_polygon_f, polyline, Clip, FillRule, Point};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Stroke {
+    pub width: f32, //In screen pixels, so zooming out keeps outlines legible
+    pub cap: LineCap,
+    pub join: LineJoin,
+    pub miter_limit: f32, //Longest miter allowed, as a multiple of width, before falling back to bevel
+    #[serde(default)]
+    pub dash: Vec<f32>, //Alternating on/off lengths in screen pixels; empty for a solid line
+    #[serde(default)]
+    pub dash_offset: f32, //How far into the pattern the outline starts
+}
+
+impl Default for Stroke {
+    fn default() -> Stroke {
+        Stroke {
+            width: 1.,
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+            miter_limit: 4.,
+            dash: Vec::new(),
+            dash_offset: 0.,
+        }
+    }
+}
+
+pub const MIN_DASH: f32 = 0.01; //Shorter dashes and gaps are lengthened to this, so a pattern can't stall on a long outline
+
+impl Stroke {
+    pub fn is_thick(&self) -> bool { //Hairlines go through the one pixel rasterizers instead
+        self.width > 1.
+    }
+    pub fn is_dashed(&self) -> bool {
+        !self.dash.is_empty() && self.dash.iter().all(|&d| d >= 0. && d.is_finite()) && self.dash.iter().sum::<f32>() > 0.
+    }
+    pub fn reach(&self) -> f32 { //Furthest the stroke can paint from its outline, at a miter or a square cap's corner, with a pixel to spare
+        self.width.max(1.) * self.miter_limit.max(2.) / 2. + 1.
+    }
+    pub fn dashes(&self, points: &[(f32, f32)], closed: bool, clip: &Clip) -> Vec<Vec<(f32, f32)>> { //The "on" runs of the outline that can reach into clip
+        let mut pieces = Vec::new();
+        for (piece, closed, along) in clip_polyline(points, closed, self.reach(), clip) {
+            pieces.extend(dash_polyline(&piece, closed, &self.dash, self.dash_offset + along)); //Offset so cut pieces stay in step with the pattern
+        }
+        pieces
+    }
+    fn runs(&self, points: &[(f32, f32)], closed: bool, clip: &Clip) -> Vec<(Vec<(f32, f32)>, bool)> { //The dashes, or the solid outline cut to clip
+        if self.is_dashed() {
+            return self.dashes(points, closed, clip).into_iter().map(|piece| (piece, false)).collect();
+        }
+        clip_polyline(points, closed, self.reach(), clip).into_iter().map(|(piece, closed, _)| (piece, closed)).collect()
+    }
+    pub fn draw(&self, points: &[(f32, f32)], closed: bool, clip: &Clip) -> Vec<Point> { //Screen space outline, dashed and widened as styled
+        if !self.is_thick() && !self.is_dashed() {
+            return polyline(points, closed, clip);
+        }
+        let mut pixels = Vec::new();
+        for (piece, closed) in self.runs(points, closed, clip) {
+            if self.is_thick() {
+                pixels.extend(stroke_polyline(&piece, closed, self, clip));
+            } else {
+                pixels.extend(polyline(&piece, closed, clip));
+            }
+        }
+        pixels
+    }
+    pub fn draw_aa(&self, points: &[(f32, f32)], closed: bool, clip: &Clip) -> Vec<(Point, f32)> { //Thick strokes with the share of each pixel they cover; hairlines are antialiased as Wu lines instead
+        let mut pixels = Vec::new();
+        for (piece, closed) in self.runs(points, closed, clip) {
+            pixels.extend(stroke_coverage(&piece, closed, self, clip));
+        }
+        pixels
+    }
+}
+
+type Vec2 = (f32, f32);
+
+fn sub(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn add(a: Vec2, b: Vec2) -> Vec2 {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn mul(a: Vec2, k: f32) -> Vec2 {
+    (a.0 * k, a.1 * k)
+}
+
+fn dot(a: Vec2, b: Vec2) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn normalize(a: Vec2) -> Vec2 {
+    let l = a.0.hypot(a.1);
+    (a.0 / l, a.1 / l)
+}
+
+fn normal(d: Vec2) -> Vec2 {
+    (-d.1, d.0)
+}
+
+fn disc(c: Vec2, r: f32) -> Vec<Vec2> {
+    let n = ((PI * r).ceil() as usize).max(8);
+    (0..n).map(|i| {
+        let theta = 2. * PI * i as f32 / n as f32;
+        (c.0 + r * theta.cos(), c.1 + r * theta.sin())
+    }).collect()
+}
+
+pub fn dash_polyline(points: &[Vec2], closed: bool, dash: &[f32], offset: f32) -> Vec<Vec<Vec2>> { //The "on" runs, continuous across vertices
+    let mut pattern: Vec<f32> = dash.iter().map(|&d| if d > 0. { d.max(MIN_DASH) } else { 0. }).collect();
+    if pattern.len() % 2 == 1 {
+        pattern.extend_from_within(..); //An odd pattern repeats with on and off swapped
+    }
+    let total: f32 = pattern.iter().sum();
+    let mut pieces: Vec<Vec<Vec2>> = Vec::new();
+    if points.is_empty() || total <= 0. {
+        return pieces;
+    }
+    let mut i = 0;
+    let mut r = offset % total;
+    if r < 0. {
+        r += total;
+    }
+    while r >= pattern[i] {
+        r -= pattern[i];
+        i = (i + 1) % pattern.len();
+    }
+    r = pattern[i] - r; //Left in the current dash or gap
+    let mut on = i % 2 == 0;
+    let mut current: Vec<Vec2> = if on { vec![points[0]] } else { Vec::new() };
+    let segments = if closed { points.len() } else { points.len() - 1 };
+    for k in 0..segments {
+        let a = points[k];
+        let b = points[(k + 1) % points.len()];
+        let len = (b.0 - a.0).hypot(b.1 - a.1);
+        let mut pos = 0.;
+        loop {
+            let stalled = r > 0. && pos + r == pos; //Too short to move along this far out in f32; the rest of the segment keeps the current state
+            if r <= len - pos && !stalled {
+                pos += r;
+                let p = add(a, mul(sub(b, a), if len > 0. { pos / len } else { 0. }));
+                if on {
+                    current.push(p);
+                    pieces.push(::std::mem::take(&mut current));
+                } else {
+                    current = vec![p];
+                }
+                on = !on;
+                i = (i + 1) % pattern.len();
+                r = pattern[i];
+            } else {
+                r = (r - (len - pos)).max(0.);
+                if on && current.last() != Some(&b) {
+                    current.push(b);
+                }
+                break;
+            }
+        }
+    }
+    if on && current.len() > 1 { //Not a dash that would only have started at the very end
+        pieces.push(current);
+    }
+    pieces
+}
+
+fn stroke_pieces(points: &[Vec2], closed: bool, style: &Stroke) -> Vec<Vec<Vec2>> { //Segment quads, joins and caps, which overlap
+    let mut pts: Vec<Vec2> = Vec::new();
+    for &p in points {
+        if pts.last().is_none_or(|&l| l != p) {
+            pts.push(p);
+        }
+    }
+    if closed && pts.len() > 1 && pts[0] == pts[pts.len() - 1] {
+        pts.pop();
+    }
+    let h = style.width / 2.;
+    let mut pieces: Vec<Vec<Vec2>> = Vec::new();
+    if pts.len() == 1 {
+        let p = pts[0];
+        match style.cap {
+            LineCap::Butt => {},
+            LineCap::Round => pieces.push(disc(p, h)),
+            LineCap::Square => pieces.push(vec![(p.0 - h, p.1 - h), (p.0 + h, p.1 - h), (p.0 + h, p.1 + h), (p.0 - h, p.1 + h)]),
+        }
+    }
+    let segments = if closed { pts.len() } else { pts.len().saturating_sub(1) };
+    for i in 0..segments {
+        let mut a = pts[i];
+        let mut b = pts[(i + 1) % pts.len()];
+        let d = normalize(sub(b, a));
+        if !closed && style.cap == LineCap::Square {
+            if i == 0 {
+                a = sub(a, mul(d, h));
+            }
+            if i == segments - 1 {
+                b = add(b, mul(d, h));
+            }
+        }
+        let n = mul(normal(d), h);
+        pieces.push(vec![add(a, n), add(b, n), sub(b, n), sub(a, n)]);
+    }
+    if !closed && pts.len() > 1 && style.cap == LineCap::Round {
+        pieces.push(disc(pts[0], h));
+        pieces.push(disc(pts[pts.len() - 1], h));
+    }
+    let joins = if closed && pts.len() > 2 { 0..pts.len() } else { 1..pts.len().saturating_sub(1).max(1) };
+    for i in joins {
+        let v = pts[i];
+        let prev = pts[(i + pts.len() - 1) % pts.len()];
+        let next = pts[(i + 1) % pts.len()];
+        let d0 = normalize(sub(v, prev));
+        let d1 = normalize(sub(next, v));
+        let side = if cross(d0, d1) > 0. { -1. } else { 1. }; //Outer side of the turn
+        let n0 = mul(normal(d0), h * side);
+        let n1 = mul(normal(d1), h * side);
+        let cos = dot(d0, d1);
+        match style.join {
+            LineJoin::Round => pieces.push(disc(v, h)),
+            //1 / sin(half the angle between segments) is the miter length over the width
+            LineJoin::Miter if 1. + cos > 1e-6 && (2. / (1. + cos)).sqrt() <= style.miter_limit => {
+                let m = mul(add(n0, n1), 1. / (1. + cos));
+                pieces.push(vec![v, add(v, n0), add(v, m), add(v, n1)]);
+            },
+            LineJoin::Miter | LineJoin::Bevel => pieces.push(vec![v, add(v, n0), add(v, n1)]),
+        }
+    }
+    pieces
+}
+
+pub fn stroke_polyline(points: &[Vec2], closed: bool, style: &Stroke, clip: &Clip) -> Vec<Point> { //Screen space vertices
+    let mut pixels: Vec<Point> = Vec::new();
+    for piece in stroke_pieces(points, closed, style) {
+        pixels.extend(fill_polygon_f(&piece, FillRule::NonZero, clip));
+    }
+    pixels.sort();
+    pixels.dedup();
+    pixels
+}
+
+const SUBSAMPLES: i32 = 4; //Along each side of a pixel, when measuring how much of it a stroke covers
+
+pub fn stroke_coverage(points: &[Vec2], closed: bool, style: &Stroke, clip: &Clip) -> Vec<(Point, f32)> { //Same, with the share of each pixel's samples inside the stroke
+    let n = SUBSAMPLES as f32;
+    let ((x0, y0), (x1, y1)) = *clip;
+    let first = |v: i32| v.saturating_mul(SUBSAMPLES);
+    let last = |v: i32| v.saturating_mul(SUBSAMPLES).saturating_add(SUBSAMPLES - 1);
+    let samples_clip = ((first(x0), first(y0)), (last(x1), last(y1)));
+    let mut samples: Vec<Point> = Vec::new();
+    for piece in stroke_pieces(points, closed, style) {
+        //Scaled up so whole coordinates fall on an n by n grid spread evenly over each pixel
+        let scaled: Vec<Vec2> = piece.iter().map(|p| ((p.0 + 0.5) * n - 0.5, (p.1 + 0.5) * n - 0.5)).collect();
+        samples.extend(fill_polygon_f(&scaled, FillRule::NonZero, &samples_clip));
+    }
+    samples.sort();
+    samples.dedup();
+    let mut coverage: HashMap<Point, f32> = HashMap::new();
+    for (x, y) in samples {
+        *coverage.entry((x.div_euclid(SUBSAMPLES), y.div_euclid(SUBSAMPLES))).or_insert(0.) += 1. / (n * n);
+    }
+    coverage.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use util::UNCLIPPED;
+
+    fn styled(width: f32, cap: LineCap, join: LineJoin) -> Stroke {
+        Stroke { width, cap, join, ..Stroke::default() }
+    }
+
+    #[test]
+    fn width_and_caps() {
+        let line = [(0., 0.), (20., 0.)];
+        let butt = stroke_polyline(&line, false, &styled(5., LineCap::Butt, LineJoin::Miter), &UNCLIPPED);
+        assert_eq!(butt.len(), 20 * 5);
+        assert!(butt.iter().all(|&(x, y)| (0..20).contains(&x) && (-2..=2).contains(&y)));
+        let square = stroke_polyline(&line, false, &styled(5., LineCap::Square, LineJoin::Miter), &UNCLIPPED);
+        assert_eq!(square.len(), 25 * 5); //Half the width past either end
+        assert!(square.contains(&(-2, -2)) && square.contains(&(22, 2)));
+        let round = stroke_polyline(&line, false, &styled(5., LineCap::Round, LineJoin::Miter), &UNCLIPPED);
+        assert!(round.contains(&(-2, 0)) && round.contains(&(22, 0)));
+        assert!(!round.contains(&(-2, -2)) && !round.contains(&(-3, 0)));
+    }
+
+    #[test]
+    fn joins() {
+        let corner = [(0., 0.), (20., 0.), (20., 20.)];
+        let draw = |join| stroke_polyline(&corner, false, &styled(6., LineCap::Butt, join), &UNCLIPPED);
+        let (miter, round, bevel) = (draw(LineJoin::Miter), draw(LineJoin::Round), draw(LineJoin::Bevel));
+        for pixels in &[&miter, &round, &bevel] {
+            assert!(pixels.contains(&(21, -1))); //Inside every join
+        }
+        assert!(miter.contains(&(22, -2)) && round.contains(&(22, -2)) && !bevel.contains(&(22, -2)));
+        assert!(miter.contains(&(22, -3)) && !round.contains(&(22, -3)) && !bevel.contains(&(22, -3)));
+    }
+
+    #[test]
+    fn miter_limit_falls_back_to_bevel() {
+        let spike = [(0., 0.), (40., 0.), (0., 8.)]; //Miter about ten widths long
+        let reach = |limit| {
+            let style = Stroke { miter_limit: limit, ..styled(6., LineCap::Butt, LineJoin::Miter) };
+            stroke_polyline(&spike, false, &style, &UNCLIPPED).iter().map(|p| p.0).max().unwrap()
+        };
+        assert!(reach(4.) <= 43);
+        assert!(reach(20.) >= 60);
+    }
+
+    #[test]
+    fn dashes_start_at_their_phase() {
+        let pieces = dash_polyline(&[(0., 0.), (20., 0.)], false, &[4., 2.], 1.);
+        let ends: Vec<(f32, f32)> = pieces.iter().map(|p| (p[0].0, p[p.len() - 1].0)).collect();
+        assert_eq!(ends, vec![(0., 3.), (5., 9.), (11., 15.), (17., 20.)]);
+    }
+
+    #[test]
+    fn odd_patterns_swap_on_and_off() {
+        let pieces = dash_polyline(&[(0., 0.), (12., 0.)], false, &[1., 2., 3.], 0.);
+        let ends: Vec<(f32, f32)> = pieces.iter().map(|p| (p[0].0, p[p.len() - 1].0)).collect();
+        assert_eq!(ends, vec![(0., 1.), (3., 6.), (7., 9.)]);
+    }
+
+    #[test]
+    fn dashes_continue_across_vertices() {
+        let pieces = dash_polyline(&[(0., 0.), (3., 0.), (3., 10.)], false, &[5., 1.], 0.);
+        assert_eq!(pieces[0], vec![(0., 0.), (3., 0.), (3., 2.)]);
+        assert_eq!(pieces[1], vec![(3., 3.), (3., 8.)]);
+    }
+
+    #[test]
+    fn closed_dashes_include_the_closing_side() {
+        let square = [(0., 0.), (10., 0.), (10., 10.), (0., 10.)];
+        let pieces = dash_polyline(&square, true, &[5., 5.], 0.);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[3], vec![(0., 10.), (0., 5.)]);
+        let open = dash_polyline(&square, false, &[5., 5.], 0.);
+        assert_eq!(open.len(), 3);
+    }
+
+    #[test]
+    fn tiny_dashes_are_lengthened() {
+        let pieces = dash_polyline(&[(0., 0.), (100., 0.)], false, &[1e-5, 1e-5], 0.);
+        assert_eq!(pieces.len(), 5000);
+        assert!(!Stroke { dash: vec![0., 0.], ..Stroke::default() }.is_dashed());
+        assert!(!Stroke { dash: vec![1., ::std::f32::NAN], ..Stroke::default() }.is_dashed());
+    }
+
+    #[test]
+    fn coverage_of_partial_rows() {
+        let line = [(0., 0.25), (10., 0.25)]; //Two wide, so it covers from -0.75 to 1.25
+        let covered: HashMap<Point, f32> = stroke_coverage(&line, false, &styled(2., LineCap::Butt, LineJoin::Miter), &UNCLIPPED).into_iter().collect();
+        assert_eq!(covered[&(5, -1)], 0.25);
+        assert_eq!(covered[&(5, 0)], 1.);
+        assert_eq!(covered[&(5, 1)], 0.75);
+        let total: f32 = covered.values().sum();
+        assert!((total - 20.).abs() < 0.5);
+    }
+}
@@ -0,0 +1,224 @@
+use serde::ser::{Serialize, Serializer};
+use serde::de::{Deserialize, Deserializer};
+
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+use util::Point;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Default)]
+pub struct Angle(f32); //Stored in degrees
+
+impl Angle {
+    pub fn degrees(d: f32) -> Angle {
+        Angle(d)
+    }
+    pub fn radians(r: f32) -> Angle {
+        Angle(r.to_degrees())
+    }
+    pub fn to_degrees(self) -> f32 {
+        self.0
+    }
+    pub fn to_radians(self) -> f32 {
+        self.0.to_radians()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SavedAngle { //Saved with its unit, as {"degrees": 90.0}
+    Degrees(f32),
+    Radians(f32),
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LoadedAngle {
+    Unit(SavedAngle),
+    Bare(f32), //Rotations were saved as bare radians before angles had units
+}
+
+impl Serialize for Angle {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        SavedAngle::Degrees(self.0).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Angle {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        Ok(match LoadedAngle::deserialize(deserializer)? {
+            LoadedAngle::Unit(SavedAngle::Degrees(d)) => Angle::degrees(d),
+            LoadedAngle::Unit(SavedAngle::Radians(r)) | LoadedAngle::Bare(r) => Angle::radians(r),
+        })
+    }
+}
+
+impl Add for Angle {
+    type Output = Angle;
+
+    fn add(self, o: Angle) -> Angle {
+        Angle(self.0 + o.0)
+    }
+}
+
+impl Sub for Angle {
+    type Output = Angle;
+
+    fn sub(self, o: Angle) -> Angle {
+        Angle(self.0 - o.0)
+    }
+}
+
+impl Neg for Angle {
+    type Output = Angle;
+
+    fn neg(self) -> Angle {
+        Angle(-self.0)
+    }
+}
+
+impl AddAssign for Angle {
+    fn add_assign(&mut self, o: Angle) {
+        self.0 += o.0;
+    }
+}
+
+impl SubAssign for Angle {
+    fn sub_assign(&mut self, o: Angle) {
+        self.0 -= o.0;
+    }
+}
+
+// Maps (x, y) to (a*x + c*y + e, b*x + d*y + f), i.e. the matrix
+// | a c e |
+// | b d f |
+// | 0 0 1 |
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { a: 1., b: 0., c: 0., d: 1., e: 0., f: 0. }
+    }
+    pub fn translate(x: f32, y: f32) -> Transform {
+        Transform { e: x, f: y, ..Transform::identity() }
+    }
+    pub fn scale(x: f32, y: f32) -> Transform {
+        Transform { a: x, d: y, ..Transform::identity() }
+    }
+    pub fn rotate(angle: Angle) -> Transform { //Clockwise on screen, since y points down
+        let s = angle.to_radians().sin();
+        let c = angle.to_radians().cos();
+        Transform { a: c, b: s, c: -s, d: c, e: 0., f: 0. }
+    }
+    pub fn shear(kx: f32, ky: f32) -> Transform { //x' = x + kx*y, y' = y + ky*x
+        Transform { b: ky, c: kx, ..Transform::identity() }
+    }
+    pub fn about(self, pivot: (f32, f32)) -> Transform { //Same transform with pivot as its fixed point
+        Transform::translate(pivot.0, pivot.1) * self * Transform::translate(-pivot.0, -pivot.1)
+    }
+    pub fn is_axis_aligned(&self) -> bool { //No rotation or shear beyond float error, so axes stay horizontal and vertical
+        self.b.abs() < 1e-5 && self.c.abs() < 1e-5
+    }
+    pub fn determinant(&self) -> f32 {
+        self.a * self.d - self.b * self.c
+    }
+    pub fn invert(&self) -> Option<Transform> {
+        let det = self.determinant();
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        Some(Transform {
+            a: self.d / det,
+            b: -self.b / det,
+            c: -self.c / det,
+            d: self.a / det,
+            e: (self.c * self.f - self.d * self.e) / det,
+            f: (self.b * self.e - self.a * self.f) / det,
+        })
+    }
+    pub fn apply(&self, p: (f32, f32)) -> (f32, f32) {
+        (self.a * p.0 + self.c * p.1 + self.e, self.b * p.0 + self.d * p.1 + self.f)
+    }
+    pub fn apply_point(&self, p: Point) -> Point {
+        let (x, y) = self.apply((p.0 as f32, p.1 as f32));
+        (x.round() as i32, y.round() as i32)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::identity()
+    }
+}
+
+impl Mul for Transform { //self * other applies other first
+    type Output = Transform;
+
+    fn mul(self, o: Transform) -> Transform {
+        Transform {
+            a: self.a * o.a + self.c * o.b,
+            b: self.b * o.a + self.d * o.b,
+            c: self.a * o.c + self.c * o.d,
+            d: self.b * o.c + self.d * o.d,
+            e: self.a * o.e + self.c * o.f + self.e,
+            f: self.b * o.e + self.d * o.f + self.f,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angles_save_their_unit() {
+        assert_eq!(::serde_json::to_string(&Angle::degrees(30.)).unwrap(), r#"{"degrees":30.0}"#);
+        assert_eq!(::serde_json::from_str::<Angle>(r#"{"degrees":30}"#).unwrap(), Angle::degrees(30.));
+        assert_eq!(::serde_json::from_str::<Angle>(r#"{"radians":3.1415927}"#).unwrap(), Angle::degrees(180.));
+        assert_eq!(::serde_json::from_str::<Angle>("3.1415927").unwrap(), Angle::degrees(180.)); //Older scenes
+        assert_eq!(::serde_json::from_str::<Angle>("0").unwrap(), Angle::default());
+    }
+
+    #[test]
+    fn full_turn_is_identity() {
+        let points = vec![(0, 0), (100, 0), (37, -250), (-999, 431)];
+        for &pivot in &[(0., 0.), (50., 50.), (-300., 1200.)] {
+            let t = Transform::rotate(Angle::degrees(360.)).about(pivot);
+            for &p in &points {
+                assert_eq!(t.apply_point(p), p);
+            }
+        }
+    }
+
+    #[test]
+    fn quarter_turn() {
+        let t = Transform::rotate(Angle::degrees(90.));
+        assert_eq!(t.apply_point((10, 0)), (0, 10));
+        assert_eq!(t.apply_point((0, 10)), (-10, 0));
+        assert_eq!(Transform::rotate(Angle::radians(::std::f32::consts::PI / 2.)).apply_point((10, 0)), (0, 10));
+    }
+
+    #[test]
+    fn pivot_is_fixed() {
+        let t = Transform::rotate(Angle::degrees(33.)).about((20., -7.));
+        assert_eq!(t.apply_point((20, -7)), (20, -7));
+    }
+
+    #[test]
+    fn inverse_round_trips() {
+        let t = Transform::translate(12., -3.) * Transform::rotate(Angle::degrees(30.)) * Transform::shear(0.2, 0.) * Transform::scale(2., 0.5);
+        let inv = t.invert().unwrap();
+        for &p in &[(0., 0.), (100., 40.), (-25., 60.)] {
+            let q = inv.apply(t.apply(p));
+            assert!((q.0 - p.0).abs() < 1e-3 && (q.1 - p.1).abs() < 1e-3);
+        }
+        assert!(Transform::scale(0., 1.).invert().is_none());
+    }
+}
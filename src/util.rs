@@ -1,11 +1,21 @@
+use transform::{Angle, Transform};
+
 pub type Point = (i32, i32);
 
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum FillRule {
+    EvenOdd,
+    NonZero,
+}
+
 pub trait VecExt {
     fn scissor(&mut self, p0: Point, p1: Point);
     fn scissor_iter(&mut self, p0: Point, p1: Point);
     fn translate(&mut self, x: i32, y: i32);
-    fn rotate(&mut self, a: f32);
+    fn rotate(&mut self, a: Angle);
+    fn rotate_about(&mut self, a: Angle, pivot: Point);
     fn scale(&mut self, a: f32, b: f32);
+    fn transform(&mut self, t: &Transform);
     fn add(self, other:Vec<Point>) -> Vec<Point>;
 }
 
@@ -56,16 +66,11 @@ impl VecExt for Vec<Point> {
             p.1 += y;
         }
     }
-    fn rotate(&mut self, a: f32) {
-        let s = a.sin();
-        let c = a.cos();
-
-        for p in self {
-            let x = p.0 as f32;
-            let y = p.1 as f32;
-            p.0 = (x * c + y * s) as i32;
-            p.1 = (x * s + y * c) as i32;
-        }
+    fn rotate(&mut self, a: Angle) {
+        self.rotate_about(a, (0, 0));
+    }
+    fn rotate_about(&mut self, a: Angle, pivot: Point) {
+        self.transform(&Transform::rotate(a).about((pivot.0 as f32, pivot.1 as f32)));
     }
     fn scale(&mut self, a: f32, b: f32) {
         assert!(a >= 0.0 && b >= 0.0);
@@ -74,6 +79,11 @@ impl VecExt for Vec<Point> {
             p.1 = (p.1 as f32 * b) as i32;
         }
     }
+    fn transform(&mut self, t: &Transform) {
+        for p in self {
+            *p = t.apply_point(*p);
+        }
+    }
     fn add(self, other:Vec<Point>) -> Vec<Point> {
         let mut mlem = self;
         mlem.extend(other);
@@ -82,69 +92,417 @@ impl VecExt for Vec<Point> {
 }
 
 
-pub fn line(p0: Point, p1: Point) -> Vec<Point> { //Starting coordinate, finishing coordinate
-    let dx = p1.0 - p0.0;
-    let dy = p1.1 - p0.1;
-    if dx < 0 {
-        return line(p1, p0);
-    }
-    let m = {
-            if dx == 0 {
-                0.0
-            }
-            else {
-                dy as f32/dx as f32
+pub type Clip = (Point, Point); //Inclusive pixel rectangle rasterized lines are cut to, top-left and bottom-right
+
+pub const UNCLIPPED: Clip = ((i32::MIN, i32::MIN), (i32::MAX, i32::MAX));
+
+const LIMIT: f32 = 1073741824.; //Subpixel coordinates are kept within 2^30 before rounding, so differences between them fit in an i32
+const GUARD: f32 = 1048576.; //Subpixel segments are only cut this far past the clip rectangle, before rounding; nearer ends are rounded as they are, so their pixels don't move
+
+fn outcode(p: Point, clip: &Clip) -> u8 { //Cohen-Sutherland region: left, right, above, below
+    let ((x0, y0), (x1, y1)) = *clip;
+    (if p.0 < x0 { 1 } else if p.0 > x1 { 2 } else { 0 }) | (if p.1 < y0 { 4 } else if p.1 > y1 { 8 } else { 0 })
+}
+
+pub fn clip_contains(clip: &Clip, p: Point) -> bool {
+    outcode(p, clip) == 0
+}
+
+fn clip_rows(clip: &Clip, y0: i32, y1: i32) -> ::std::ops::Range<i32> { //Rows y0..=y1 that are inside clip
+    y0.max((clip.0).1)..y1.min((clip.1).1).saturating_add(1)
+}
+
+pub fn clip_bounds(clip: &Clip, margin: f32) -> ((f32, f32), (f32, f32)) { //Subpixel rectangle reaching margin past the clip rectangle's pixels
+    let side = |v: i32, d: f32| (v as f32 + d).clamp(-LIMIT, LIMIT);
+    let ((x0, y0), (x1, y1)) = *clip;
+    ((side(x0, -margin), side(y0, -margin)), (side(x1, 1. + margin), side(y1, 1. + margin)))
+}
+
+pub fn clip_segment(p0: (f32, f32), p1: (f32, f32), min: (f32, f32), max: (f32, f32)) -> Option<((f32, f32), (f32, f32))> { //Liang-Barsky; ends that are already inside come back unchanged
+    let (x0, y0) = (p0.0 as f64, p0.1 as f64);
+    let (dx, dy) = (p1.0 as f64 - x0, p1.1 as f64 - y0);
+    let (mut t0, mut t1) = (0f64, 1f64);
+    let edges = [
+        (-dx, x0 - min.0 as f64),
+        (dx, max.0 as f64 - x0),
+        (-dy, y0 - min.1 as f64),
+        (dy, max.1 as f64 - y0),
+    ];
+    for &(p, q) in &edges {
+        if p == 0. {
+            if q < 0. || q.is_nan() {
+                return None; //Parallel to this edge and outside it
             }
-    };
-    if m > 1.0 || m < -1.0 {
-        return line2((p1.1, p1.0), (p0.1, p0.0));
+        } else if p < 0. {
+            t0 = t0.max(q / p); //Entering
+        } else {
+            t1 = t1.min(q / p); //Leaving
+        }
     }
-    let mut points: Vec<Point> = vec!((p0.0, p0.1), (p1.0, p1.1));
-    let mut y = p0.1 as f32;
-    for x in p0.0..p1.0 {
-        y += m;
-        points.push((x, (y + 0.5).floor() as i32));
+    if t0 > t1 || t0.is_nan() {
+        return None;
+    }
+    let at = |t: f64| ((x0 + t * dx) as f32, (y0 + t * dy) as f32);
+    Some((if t0 == 0. { p0 } else { at(t0) }, if t1 == 1. { p1 } else { at(t1) }))
+}
+
+fn steps_inside(start: i64, direction: i64, lo: i64, hi: i64) -> (i64, i64) { //Range of step counts that keep start + direction * steps within lo..=hi
+    if direction > 0 { (lo - start, hi - start) } else { (start - hi, start - lo) }
+}
+
+fn div_floor(n: i128, d: i128) -> i128 {
+    let q = n / d;
+    if (n % d != 0) && ((n < 0) != (d < 0)) { q - 1 } else { q }
+}
+
+fn div_ceil(n: i128, d: i128) -> i128 {
+    -div_floor(-n, d)
+}
+
+pub fn line(p0: Point, p1: Point, clip: &Clip) -> Vec<Point> { //Starting coordinate, finishing coordinate; both included, as far as they are inside clip
+    //Always walk from the smaller endpoint so the pixels don't depend on argument order
+    let (p0, p1) = if p1 < p0 { (p1, p0) } else { (p0, p1) };
+    if outcode(p0, clip) & outcode(p1, clip) != 0 {
+        return Vec::new(); //Both ends beyond the same side
+    }
+    let (x0, y0) = (p0.0 as i64, p0.1 as i64);
+    let dx = p1.0 as i64 - x0; //Never negative after ordering
+    let dy = p1.1 as i64 - y0;
+    let sy = if dy < 0 { -1 } else { 1 };
+    let x_major = dx >= dy.abs();
+    let (major, minor) = if x_major { (dx, dy.abs()) } else { (dy.abs(), dx) };
+    if major == 0 {
+        return vec![p0];
+    }
+    //Bresenham in closed form: after k steps along the major axis the minor axis has moved
+    //floor((2 * minor * k + major) / (2 * major)) steps, so the walk can start at the first step inside clip
+    let ((cx0, cy0), (cx1, cy1)) = *clip;
+    let (xs, ys) = (steps_inside(x0, 1, cx0 as i64, cx1 as i64), steps_inside(y0, sy, cy0 as i64, cy1 as i64));
+    let (along, across) = if x_major { (xs, ys) } else { (ys, xs) };
+    let (two_major, two_minor) = (2 * major as i128, 2 * minor as i128);
+    let (mut k0, mut k1) = (along.0.max(0) as i128, along.1.min(major) as i128);
+    if minor == 0 {
+        if across.0 > 0 || across.1 < 0 {
+            return Vec::new();
+        }
+    } else {
+        k0 = k0.max(div_ceil(two_major * across.0 as i128 - major as i128, two_minor));
+        k1 = k1.min(div_ceil(two_major * (across.1 as i128 + 1) - major as i128, two_minor) - 1);
+    }
+    if k0 > k1 {
+        return Vec::new();
+    }
+    let num = two_minor * k0 + major as i128;
+    let (mut m, mut rem) = ((num / two_major) as i64, (num % two_major) as i64);
+    let mut points: Vec<Point> = Vec::with_capacity((k1 - k0 + 1) as usize);
+    for k in k0 as i64..k1 as i64 + 1 {
+        let (x, y) = if x_major { (x0 + k, y0 + sy * m) } else { (x0 + m, y0 + sy * k) };
+        points.push((x as i32, y as i32));
+        rem += 2 * minor;
+        if rem >= 2 * major {
+            rem -= 2 * major;
+            m += 1;
+        }
     }
     points
 }
 
-fn line2(p0: Point, p1: Point) -> Vec<Point> {
-    let dx = p1.0 - p0.0;
-    let dy = p1.1 - p0.1;
-    if dx < 0 {
-        return line2(p1, p0);
+fn segment(p0: (f32, f32), p1: (f32, f32), clip: &Clip) -> Vec<Point> { //Subpixel ends rounded to the nearest pixel; ends far outside are first cut to the guard band
+    let (min, max) = clip_bounds(clip, GUARD);
+    match clip_segment(p0, p1, min, max) {
+        Some((a, b)) => line((a.0.round() as i32, a.1.round() as i32), (b.0.round() as i32, b.1.round() as i32), clip),
+        None => Vec::new(),
     }
-    let m = {
-            if dx == 0 {
-                0.0
-            }
-            else {
-                dy as f32/dx as f32
-            }
+}
+
+fn fpart(x: f32) -> f32 {
+    x - x.floor()
+}
+
+fn rfpart(x: f32) -> f32 {
+    1. - fpart(x)
+}
+
+pub fn line_aa(p0: (f32, f32), p1: (f32, f32), clip: &Clip) -> Vec<(Point, f32)> { //Xiaolin Wu; subpixel endpoints, pixels with coverage
+    //Cut just outside clip, so the ends drawn at the cuts fall outside it
+    let (min, max) = clip_bounds(clip, 2.);
+    let (p0, p1) = match clip_segment(p0, p1, min, max) {
+        Some(s) => s,
+        None => return Vec::new(),
     };
-    let mut points: Vec<Point> = vec!((p1.1, p1.0), (p0.1, p0.0));
-    let mut y = p0.1 as f32;
-    for x in p0.0..p1.0 {
-        y += m;
-        points.push(((y + 0.5).floor() as i32, x));
+    let steep = (p1.1 - p0.1).abs() > (p1.0 - p0.0).abs();
+    let (mut a, mut b) = if steep { ((p0.1, p0.0), (p1.1, p1.0)) } else { (p0, p1) };
+    if a.0 > b.0 {
+        ::std::mem::swap(&mut a, &mut b);
+    }
+    let dx = b.0 - a.0;
+    let gradient = if dx == 0. { 1. } else { (b.1 - a.1) / dx };
+    let mut points: Vec<(Point, f32)> = Vec::new();
+    {
+        let mut plot = |x: i32, y: i32, c: f32| {
+            let p = if steep { (y, x) } else { (x, y) };
+            if c > 0. && clip_contains(clip, p) {
+                points.push((p, c));
+            }
+        };
+
+        let xend = (a.0 + 0.5).floor(); //Rounding halves up either side of zero, so an end on a pixel's edge isn't given the pixel beyond
+        let yend = a.1 + gradient * (xend - a.0);
+        let xgap = rfpart(a.0 + 0.5);
+        let x0 = xend as i32;
+        plot(x0, yend.floor() as i32, rfpart(yend) * xgap);
+        plot(x0, yend.floor() as i32 + 1, fpart(yend) * xgap);
+        let mut intery = yend + gradient;
+
+        let xend = (b.0 + 0.5).floor();
+        let yend = b.1 + gradient * (xend - b.0);
+        let xgap = fpart(b.0 + 0.5);
+        let x1 = xend as i32;
+        plot(x1, yend.floor() as i32, rfpart(yend) * xgap);
+        plot(x1, yend.floor() as i32 + 1, fpart(yend) * xgap);
+
+        for x in x0 + 1..x1 {
+            plot(x, intery.floor() as i32, rfpart(intery));
+            plot(x, intery.floor() as i32 + 1, fpart(intery));
+            intery += gradient;
+        }
+    }
+    points
+}
+
+pub fn polygon_aa(corners: &[(f32, f32)], clip: &Clip) -> Vec<(Point, f32)> {
+    let mut points: Vec<(Point, f32)> = Vec::new();
+    for i in 0..corners.len() {
+        points.extend(line_aa(corners[i], corners[(i + 1) % corners.len()], clip));
     }
     points
 }
 
-pub fn rect(p0: Point, p1: Point) -> Vec<Point> {
-    let mut points = line((p0.0, p0.1), (p1.0, p0.1));
-    points.extend(line((p0.0, p0.1), (p0.0, p1.1)));
-    points.extend(line((p1.0, p0.1), (p1.0, p1.1)));
-    points.extend(line((p0.0, p1.1), (p1.0, p1.1)));
+pub fn polyline_aa(points: &[(f32, f32)], clip: &Clip) -> Vec<(Point, f32)> { //Open; the last point doesn't join back to the first
+    let mut pixels: Vec<(Point, f32)> = Vec::new();
+    for w in points.windows(2) {
+        pixels.extend(line_aa(w[0], w[1], clip));
+    }
+    pixels
+}
+
+pub fn rect(p0: Point, p1: Point, clip: &Clip) -> Vec<Point> {
+    let mut points = line((p0.0, p0.1), (p1.0, p0.1), clip);
+    points.extend(line((p0.0, p0.1), (p0.0, p1.1), clip));
+    points.extend(line((p1.0, p0.1), (p1.0, p1.1), clip));
+    points.extend(line((p0.0, p1.1), (p1.0, p1.1), clip));
     points
 }
 
-pub fn polygon(corners: &Vec<Point>) -> Vec<Point> {
+pub fn fill_rect(p0: Point, p1: Point, clip: &Clip) -> Vec<Point> {
+    let mut points: Vec<Point> = Vec::new();
+    for y in clip_rows(clip, p0.1.min(p1.1), p0.1.max(p1.1)) {
+        points.extend(span(p0.0.min(p1.0), p0.0.max(p1.0), y, clip));
+    }
+    points
+}
+
+fn span(x0: i32, x1: i32, y: i32, clip: &Clip) -> Vec<Point> { //Inclusive on both ends, as far as they are inside clip
+    if y < (clip.0).1 || y > (clip.1).1 {
+        return Vec::new();
+    }
+    (x0.max((clip.0).0)..x1.min((clip.1).0).saturating_add(1)).map(|x| (x, y)).collect()
+}
+
+pub fn polygon(corners: &[Point], clip: &Clip) -> Vec<Point> {
     let mut points: Vec<Point> = Vec::new();
     for x in corners.windows(2) {
-        points.extend(line(x[0], x[1]));
+        points.extend(line(x[0], x[1], clip));
+    }
+    points.extend(line(corners[0], corners[corners.len() - 1], clip));
+    points
+}
+
+pub fn polyline(points: &[(f32, f32)], closed: bool, clip: &Clip) -> Vec<Point> { //Subpixel vertices, rounded to the nearest pixel
+    let mut pixels = Vec::new();
+    if points.len() == 1 {
+        pixels.extend(segment(points[0], points[0], clip));
+    }
+    for w in points.windows(2) {
+        pixels.extend(segment(w[0], w[1], clip));
+    }
+    if closed && points.len() > 2 {
+        pixels.extend(segment(points[points.len() - 1], points[0], clip));
+    }
+    pixels
+}
+
+pub type Run = (Vec<(f32, f32)>, bool, f32); //Points of part of an outline, whether it is closed and how far along the outline it starts
+
+pub fn clip_polyline(points: &[(f32, f32)], closed: bool, margin: f32, clip: &Clip) -> Vec<Run> { //The runs within margin of clip, whether each is still closed, and how far along the outline each starts
+    let (min, max) = clip_bounds(clip, margin);
+    let inside = |p: &(f32, f32)| p.0 >= min.0 && p.0 <= max.0 && p.1 >= min.1 && p.1 <= max.1;
+    if points.iter().all(inside) {
+        return vec![(points.to_vec(), closed, 0.)];
+    }
+    let n = points.len();
+    let segments = if closed { n } else { n - 1 };
+    let mut pieces: Vec<Run> = Vec::new();
+    let mut joined = false; //Whether the last piece reached the end of the previous segment uncut
+    let mut along = 0.;
+    for k in 0..segments {
+        let (a, b) = (points[k], points[(k + 1) % n]);
+        match clip_segment(a, b, min, max) {
+            Some((c, d)) => {
+                match pieces.last_mut() {
+                    Some(&mut (ref mut piece, _, _)) if joined && c == a => piece.push(d),
+                    _ => pieces.push((vec![c, d], false, along + (c.0 - a.0).hypot(c.1 - a.1))),
+                }
+                joined = d == b;
+            },
+            None => joined = false,
+        }
+        along += (b.0 - a.0).hypot(b.1 - a.1);
+    }
+    if closed && joined && pieces.len() > 1 && pieces[0].0[0] == points[0] {
+        //The outline runs on through its first vertex, so the last piece carries on into the first
+        let first = pieces.remove(0);
+        pieces.last_mut().unwrap().0.extend(first.0.into_iter().skip(1));
+    }
+    pieces
+}
+
+#[derive(Clone, Copy)]
+struct Edge {
+    y_min: i32,
+    y_max: i32,
+    x: f32,
+    dxdy: f32,
+    winding: i32,
+}
+
+fn edge(p0: (f32, f32), p1: (f32, f32)) -> Option<Edge> {
+    let (top, bottom, winding) = if p0.1 < p1.1 { (p0, p1, 1) } else { (p1, p0, -1) };
+    //Rows in [top, bottom); pixels are centered on whole coordinates, as outlines place them, so fills meet their outlines
+    let y_min = top.1.ceil() as i32;
+    let y_max = bottom.1.ceil() as i32;
+    if y_min >= y_max {
+        return None;
+    }
+    let dxdy = (bottom.0 - top.0) / (bottom.1 - top.1);
+    Some(Edge {
+        y_min,
+        y_max,
+        x: top.0 + dxdy * (y_min as f32 - top.1),
+        dxdy,
+        winding,
+    })
+}
+
+pub fn fill_polygon(corners: &[Point], rule: FillRule, clip: &Clip) -> Vec<Point> { //Edge loop, winding rule
+    let corners: Vec<_> = corners.iter().map(|p| (p.0 as f32, p.1 as f32)).collect();
+    fill_polygon_f(&corners, rule, clip)
+}
+
+pub fn fill_polygon_f(corners: &[(f32, f32)], rule: FillRule, clip: &Clip) -> Vec<Point> { //Same, with subpixel corners
+    fill_loops_f(&[corners], rule, clip)
+}
+
+pub fn fill_loops_f<L: AsRef<[(f32, f32)]>>(loops: &[L], rule: FillRule, clip: &Clip) -> Vec<Point> { //Several edge loops filled together, so holes and overlaps follow the winding rule
+    let mut points: Vec<Point> = Vec::new();
+    let mut edges: Vec<Edge> = Vec::new();
+    for corners in loops {
+        let corners = corners.as_ref();
+        if corners.len() < 3 {
+            continue;
+        }
+        for i in 0..corners.len() {
+            edges.extend(edge(corners[i], corners[(i + 1) % corners.len()]));
+        }
+    }
+    //Edges starting above clip are moved down to its first row, and only rows inside it are walked
+    let ((left, top), (right, bottom)) = *clip;
+    edges.retain(|e| e.y_max > top && e.y_min <= bottom);
+    for e in edges.iter_mut() {
+        if e.y_min < top {
+            e.x += e.dxdy * (top as f32 - e.y_min as f32);
+            e.y_min = top;
+        }
+    }
+    if edges.is_empty() {
+        return points;
+    }
+    edges.sort_by_key(|e| e.y_min);
+    let y_end = edges.iter().map(|e| e.y_max).max().unwrap().min(bottom.saturating_add(1));
+    let mut active: Vec<Edge> = Vec::new();
+    let mut next = 0;
+    let mut y = edges[0].y_min;
+    while y < y_end {
+        while next < edges.len() && edges[next].y_min == y {
+            active.push(edges[next]);
+            next += 1;
+        }
+        active.retain(|e| e.y_max > y);
+        active.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+        let mut winding = 0;
+        for pair in active.windows(2) {
+            winding += match rule {
+                FillRule::EvenOdd => 1,
+                FillRule::NonZero => pair[0].winding,
+            };
+            let inside = match rule {
+                FillRule::EvenOdd => winding % 2 != 0,
+                FillRule::NonZero => winding != 0,
+            };
+            if inside {
+                let x0 = (pair[0].x.ceil() as i32).max(left);
+                let x1 = (pair[1].x.ceil() as i32).min(right.saturating_add(1));
+                points.extend((x0..x1).map(|x| (x, y)));
+            }
+        }
+        for e in active.iter_mut() {
+            e.x += e.dxdy;
+        }
+        y += 1;
+    }
+    points
+}
+
+type TriangleEdge = ((f32, f32), (f32, f32), usize, bool); //Start, end, the vertex opposite it and whether it is a top or left edge
+
+fn edge_function(a: (f32, f32), b: (f32, f32), p: (f32, f32)) -> f32 { //Twice the signed area of a, b, p; positive when p is clockwise from a to b on screen
+    (b.0 - a.0) * (p.1 - a.1) - (b.1 - a.1) * (p.0 - a.0)
+}
+
+pub fn triangle(v: [(f32, f32); 3], clip: &Clip) -> Vec<(Point, [f32; 3])> { //Pixels in clip whose centers are inside, with barycentric weights of the three vertices
+    let mut points = Vec::new();
+    let area = edge_function(v[0], v[1], v[2]);
+    if area == 0. {
+        return points;
+    }
+    //Walk the edges clockwise, so inside is where all three edge functions are positive
+    let order = if area > 0. { [0, 1, 2] } else { [0, 2, 1] };
+    let area = area.abs();
+    let edges: Vec<TriangleEdge> = (0..3).map(|i| {
+        let (a, b) = (v[order[(i + 1) % 3]], v[order[(i + 2) % 3]]);
+        //Top-left rule: a pixel center exactly on an edge belongs to it only if the edge is a top or left one,
+        //so triangles sharing an edge never both draw it
+        let top_left = (a.1 == b.1 && b.0 > a.0) || b.1 < a.1;
+        (a, b, order[i], top_left)
+    }).collect();
+    let x0 = ((v[0].0.min(v[1].0).min(v[2].0) - 0.5).ceil() as i32).max((clip.0).0);
+    let x1 = ((v[0].0.max(v[1].0).max(v[2].0) - 0.5).floor() as i32).min((clip.1).0);
+    let y0 = (v[0].1.min(v[1].1).min(v[2].1) - 0.5).ceil() as i32;
+    let y1 = (v[0].1.max(v[1].1).max(v[2].1) - 0.5).floor() as i32;
+    for y in clip_rows(clip, y0, y1) {
+        for x in x0..x1.saturating_add(1) {
+            let p = (x as f32 + 0.5, y as f32 + 0.5); //Sample at pixel centers
+            let mut weights = [0.; 3];
+            let inside = edges.iter().all(|&(a, b, opposite, top_left)| {
+                let w = edge_function(a, b, p);
+                weights[opposite] = w / area;
+                w > 0. || (w == 0. && top_left)
+            });
+            if inside {
+                points.push(((x, y), weights));
+            }
+        }
     }
-    points.extend(line(corners[0], corners[corners.len() - 1]));
     points
 }
 
@@ -157,32 +515,530 @@ fn ellipse_points(x: i32, y: i32, p0: Point) -> Vec<Point> {
     points
 }
 
-pub fn ellipse(p0: Point, a: i32, b: i32) -> Vec<Point> { //Center coordinate, width, height
-    let mut x = 0;
-    let mut y = b;
-    let mut d1 = (b.pow(2)) - ((a.pow(2))*b) + (a.pow(2))/4;
-    let mut points = ellipse_points(x, y, p0);
-    while (a.pow(2))*y >(b.pow(2))*(x+1){ //y- 0.5
+fn ellipse_spans(extents: &[i32], p0: Point, clip: &Clip) -> Vec<Point> {
+    let mut points: Vec<Point> = Vec::new();
+    for (y, x) in extents.iter().enumerate() {
+        let y = y as i32;
+        points.extend(span(-x + p0.0, x + p0.0, y + p0.1, clip));
+        if y != 0 {
+            points.extend(span(-x + p0.0, x + p0.0, -y + p0.1, clip));
+        }
+    }
+    points
+}
+
+const WALK_LIMIT: i32 = 1024; //Radii past this skip the midpoint walk, whose cost grows with the whole curve, for conic rows limited to clip
+
+fn conic_axes(p0: Point, a: i32, b: i32) -> ((f32, f32), (f32, f32), (f32, f32)) { //An axis-aligned ellipse as conic_ellipse takes it
+    ((p0.0 as f32, p0.1 as f32), (a as f32, 0.), (0., b as f32))
+}
+
+pub fn fill_ellipse(p0: Point, a: i32, b: i32, clip: &Clip) -> Vec<Point> { //Center coordinate, width, height
+    if a < 0 || b < 0 {
+        return Vec::new();
+    }
+    if a.max(b) > WALK_LIMIT {
+        let (c, u, v) = conic_axes(p0, a, b);
+        return fill_conic_ellipse(c, u, v, clip);
+    }
+    //The widest x the walk reaches on each row
+    let mut extents = vec![0; b as usize + 1];
+    for (x, y) in ellipse_steps(a, b) {
+        extents[y as usize] = extents[y as usize].max(x);
+    }
+    ellipse_spans(&extents, p0, clip)
+}
+
+pub fn ellipse(p0: Point, a: i32, b: i32, clip: &Clip) -> Vec<Point> { //Center coordinate, width, height; small ones are walked whole and cut to clip, large ones only solved on its rows
+    if a.max(b) > WALK_LIMIT {
+        let (c, u, v) = conic_axes(p0, a, b);
+        return conic_ellipse(c, u, v, clip);
+    }
+    let mut points: Vec<Point> = ellipse_steps(a, b).into_iter().flat_map(|(x, y)| ellipse_points(x, y, p0)).collect();
+    points.retain(|&p| clip_contains(clip, p));
+    points
+}
+
+fn ellipse_steps(a: i32, b: i32) -> Vec<Point> { //Midpoint walk of one quadrant from (0, b) to (a, 0), shared by outline and fill
+    //Decision variables grow with the fourth power of the radii, so zoomed ellipses need i64
+    let (a2, b2) = (a as i64 * a as i64, b as i64 * b as i64);
+    let mut x: i64 = 0;
+    let mut y = b as i64;
+    let mut d1 = b2 - a2 * y + a2 / 4;
+    let mut steps = vec![(0, b)];
+    while a2 * y > b2 * (x + 1) { //y- 0.5
         if d1 < 0 {
-            d1 += (b.pow(2))*(2*x+3);
+            d1 += b2 * (2 * x + 3);
         }
         else {
-            d1 += (b.pow(2))*(2*x+3)+(a.pow(2))*(-2*y+2);
-            y-=1;
+            d1 += b2 * (2 * x + 3) + a2 * (-2 * y + 2);
+            y -= 1;
         }
-        x+=1;
-        points.extend(ellipse_points(x, y, p0));
+        x += 1;
+        steps.push((x as i32, y as i32));
     }
-    let mut d2 = b.pow(2) * ((x).pow(2)) + a.pow(2) * ((y - 1).pow(2)) - (a.pow(2) * b.pow(2));
+    let mut d2 = b2 * x * x + a2 * (y - 1) * (y - 1) - a2 * b2;
     while y > 0 {
         if d2 < 0 {
-            d2 += b.pow(2) * (2 * x + 2) + a.pow(2) * (-2 * y + 3);
+            d2 += b2 * (2 * x + 2) + a2 * (-2 * y + 3);
             x += 1;
         } else {
-            d2 += a.pow(2) * (-2 * y + 3);
+            d2 += a2 * (-2 * y + 3);
         }
         y -= 1;
-        points.extend(ellipse_points(x, y, p0));
+        steps.push((x as i32, y as i32));
+    }
+    steps
+}
+
+fn conic_rows(c: (f32, f32), u: (f32, f32), v: (f32, f32), range: ::std::ops::Range<i32>) -> Vec<(i32, f32, f32)> { //Each row's left and right crossings of the ellipse, for rows in range
+    //Points c + u cos t + v sin t satisfy A dx^2 + B dx dy + C dy^2 = F, with dx, dy taken from the center
+    let a = u.1 * u.1 + v.1 * v.1;
+    let b = -2. * (u.0 * u.1 + v.0 * v.1);
+    let cc = u.0 * u.0 + v.0 * v.0;
+    let det = u.0 * v.1 - v.0 * u.1;
+    let f = det * det;
+    let mut rows = Vec::new();
+    if a < 1e-6 { //Flattened onto a horizontal line
+        let w = cc.sqrt();
+        rows.push((c.1.round() as i32, c.0 - w, c.0 + w));
+        return rows;
+    }
+    let h = a.sqrt(); //Half the height
+    let (top, bottom) = ((c.1 - h).ceil() as i32, (c.1 + h).floor() as i32);
+    for y in top.max(range.start)..bottom.saturating_add(1).min(range.end) {
+        let dy = y as f32 - c.1;
+        let disc = ((b * b - 4. * a * cc) * dy * dy + 4. * a * f).max(0.).sqrt();
+        rows.push((y, c.0 + (-b * dy - disc) / (2. * a), c.0 + (-b * dy + disc) / (2. * a)));
+    }
+    if top > bottom { //Thinner than a row and between two
+        rows.push((c.1.round() as i32, c.0, c.0));
+    }
+    rows
+}
+
+fn near_rows(clip: &Clip) -> ::std::ops::Range<i32> { //Clip's rows and one either side, which an outline needs to join up its rows inside clip
+    (clip.0).1.saturating_sub(1)..(clip.1).1.saturating_add(2)
+}
+
+fn row_outline(rows: &[(i32, i32, i32)], clip: &Clip) -> Vec<Point> { //Boundary of a convex shape given each row's leftmost and rightmost pixel
+    let mut points = Vec::new();
+    for i in 0..rows.len() {
+        let (y, l, r) = rows[i];
+        if i == 0 || i == rows.len() - 1 {
+            points.extend(span(l, r, y, clip));
+            continue;
+        }
+        //Run out toward the neighbouring rows' ends, so the outline stays connected where it is nearly horizontal
+        let (above, below) = (rows[i - 1], rows[i + 1]);
+        let l_end = l.max(above.1.max(below.1) - 1);
+        let r_start = r.min(above.2.min(below.2) + 1);
+        if l_end + 1 >= r_start {
+            points.extend(span(l, r, y, clip));
+        } else {
+            points.extend(span(l, l_end, y, clip));
+            points.extend(span(r_start, r, y, clip));
+        }
     }
     points
 }
+
+fn round_rows(rows: &[(i32, f32, f32)]) -> Vec<(i32, i32, i32)> { //Nearest pixel to each row's crossings, shared by outline and fill so they line up
+    rows.iter().map(|&(y, l, r)| (y, l.round() as i32, r.round() as i32)).collect()
+}
+
+fn row_fill(rows: &[(i32, i32, i32)], clip: &Clip) -> Vec<Point> {
+    let mut points = Vec::new();
+    for &(y, l, r) in rows {
+        points.extend(span(l, r, y, clip));
+    }
+    points
+}
+
+pub fn conic_ellipse(c: (f32, f32), u: (f32, f32), v: (f32, f32), clip: &Clip) -> Vec<Point> { //Center, ends of two conjugate semi-axes; any rotation or shear
+    row_outline(&round_rows(&conic_rows(c, u, v, near_rows(clip))), clip)
+}
+
+pub fn fill_conic_ellipse(c: (f32, f32), u: (f32, f32), v: (f32, f32), clip: &Clip) -> Vec<Point> { //Same, filled with spans
+    row_fill(&round_rows(&conic_rows(c, u, v, clip_rows(clip, i32::MIN, i32::MAX))), clip)
+}
+
+fn corner_inset(y: f32, top: f32, bottom: f32, upper: (f32, f32), lower: (f32, f32)) -> f32 { //How far a side is pushed in by its corners on row y
+    let quarter = |r: (f32, f32), d: f32| r.0 * (1. - (1. - (d / r.1).powi(2)).max(0.).sqrt());
+    if y < top + upper.1 {
+        quarter(upper, top + upper.1 - y)
+    } else if y > bottom - lower.1 {
+        quarter(lower, y - (bottom - lower.1))
+    } else {
+        0.
+    }
+}
+
+fn round_rect_rows(p0: Point, p1: Point, radii: [(f32, f32); 4], range: ::std::ops::Range<i32>) -> Vec<(i32, f32, f32)> { //For rows in range
+    let (x0, x1) = (p0.0.min(p1.0) as f32, p0.0.max(p1.0) as f32);
+    let (y0, y1) = (p0.1.min(p1.1), p0.1.max(p1.1));
+    let [tl, tr, br, bl] = radii;
+    (y0.max(range.start)..y1.saturating_add(1).min(range.end)).map(|y| {
+        let (yf, top, bottom) = (y as f32, y0 as f32, y1 as f32);
+        (y, x0 + corner_inset(yf, top, bottom, tl, bl), x1 - corner_inset(yf, top, bottom, tr, br))
+    }).collect()
+}
+
+pub fn round_rect(p0: Point, p1: Point, radii: [(f32, f32); 4], clip: &Clip) -> Vec<Point> { //Opposite corners, then x and y radii clockwise from top-left
+    row_outline(&round_rows(&round_rect_rows(p0, p1, radii, near_rows(clip))), clip)
+}
+
+pub fn fill_round_rect(p0: Point, p1: Point, radii: [(f32, f32); 4], clip: &Clip) -> Vec<Point> {
+    row_fill(&round_rows(&round_rect_rows(p0, p1, radii, clip_rows(clip, i32::MIN, i32::MAX))), clip)
+}
+
+pub fn ellipse_arc(p0: Point, a: i32, b: i32, start: f32, sweep: f32, clip: &Clip) -> Vec<Point> { //Center coordinate, width, height, parametric angles in radians
+    let two_pi = 2. * ::std::f32::consts::PI;
+    if sweep.abs() >= two_pi {
+        return ellipse(p0, a, b, clip);
+    }
+    let (start, sweep) = if sweep < 0. { (start + sweep, -sweep) } else { (start, sweep) };
+    ellipse(p0, a, b, clip).into_iter().filter(|p| {
+        //Parametric angle of the pixel, so it agrees with (a cos, b sin) end points
+        let theta = ((p.1 as f32 - p0.1 as f32) * a as f32).atan2((p.0 as f32 - p0.0 as f32) * b as f32);
+        let mut d = (theta - start) % two_pi;
+        if d < 0. {
+            d += two_pi;
+        }
+        d <= sweep
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut points: Vec<Point>) -> Vec<Point> {
+        points.sort();
+        points
+    }
+
+    #[test]
+    fn line_octants() {
+        let golden: Vec<(Point, Vec<Point>)> = vec![
+            ((5, 2), vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)]),
+            ((2, 5), vec![(0, 0), (0, 1), (1, 2), (1, 3), (2, 4), (2, 5)]),
+            ((-2, 5), vec![(0, 0), (0, 1), (-1, 2), (-1, 3), (-2, 4), (-2, 5)]),
+            ((-5, 2), vec![(0, 0), (-1, 0), (-2, 1), (-3, 1), (-4, 2), (-5, 2)]),
+            ((-5, -2), vec![(0, 0), (-1, 0), (-2, -1), (-3, -1), (-4, -2), (-5, -2)]),
+            ((-2, -5), vec![(0, 0), (0, -1), (-1, -2), (-1, -3), (-2, -4), (-2, -5)]),
+            ((2, -5), vec![(0, 0), (0, -1), (1, -2), (1, -3), (2, -4), (2, -5)]),
+            ((5, -2), vec![(0, 0), (1, 0), (2, -1), (3, -1), (4, -2), (5, -2)]),
+            ((4, 0), vec![(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]),
+            ((0, -3), vec![(0, 0), (0, -1), (0, -2), (0, -3)]),
+            ((3, 3), vec![(0, 0), (1, 1), (2, 2), (3, 3)]),
+            ((-3, 3), vec![(0, 0), (-1, 1), (-2, 2), (-3, 3)]),
+            ((0, 0), vec![(0, 0)]),
+        ];
+        for (end, expected) in golden {
+            assert_eq!(sorted(line((0, 0), end, &UNCLIPPED)), sorted(expected.clone()), "(0, 0) -> {:?}", end);
+            let mut shifted = expected;
+            shifted.translate(7, -11);
+            assert_eq!(sorted(line((7, -11), (end.0 + 7, end.1 - 11), &UNCLIPPED)), sorted(shifted));
+        }
+    }
+
+    #[test]
+    fn line_order_independent() {
+        for &(p0, p1) in &[((0, 0), (4, 1)), ((3, -2), (-5, 7)), ((-6, -6), (9, 1)), ((10, 10), (11, 30))] {
+            assert_eq!(line(p0, p1, &UNCLIPPED), line(p1, p0, &UNCLIPPED));
+        }
+    }
+
+    #[test]
+    fn line_exact_and_unique() {
+        for &(p0, p1) in &[((0, 0), (17, 5)), ((-40, 13), (22, -90)), ((5, 5), (5, 60))] {
+            let points = line(p0, p1, &UNCLIPPED);
+            assert!(points.contains(&p0) && points.contains(&p1));
+            let n = (p1.0 - p0.0).abs().max((p1.1 - p0.1).abs()) as usize + 1;
+            assert_eq!(points.len(), n);
+            let mut unique = points.clone();
+            unique.sort();
+            unique.dedup();
+            assert_eq!(unique.len(), n);
+        }
+    }
+
+    #[test]
+    fn ellipse_fill_matches_outline() {
+        //Every row of the fill runs exactly between the outline's outermost pixels on that row
+        use std::collections::BTreeMap;
+        for &(a, b) in &[(40, 25), (25, 40), (7, 7), (1, 9), (12, 0), (0, 0)] {
+            let mut rows: BTreeMap<i32, (i32, i32)> = BTreeMap::new();
+            for (x, y) in ellipse((3, -4), a, b, &UNCLIPPED) {
+                let e = rows.entry(y).or_insert((x, x));
+                *e = (e.0.min(x), e.1.max(x));
+            }
+            let expected: Vec<Point> = rows.iter().flat_map(|(&y, &(l, r))| (l..r + 1).map(move |x| (x, y))).collect();
+            assert_eq!(sorted(fill_ellipse((3, -4), a, b, &UNCLIPPED)), sorted(expected), "{} by {}", a, b);
+        }
+    }
+
+    #[test]
+    fn wu_coverage_sums_per_step() {
+        use std::collections::BTreeMap;
+        for &(p0, p1) in &[((0.3, 0.2), (10.7, 4.1)), ((2.6, -3.4), (-1.2, 9.8)), ((-5.5, 7.25), (6.5, -4.75))] {
+            //Each step along the major axis is covered by as much of the line as crosses that pixel: all of it between the ends, part at them
+            let steep = (p1.1 - p0.1 as f32).abs() > (p1.0 - p0.0 as f32).abs();
+            let major = |p: (f32, f32)| if steep { p.1 } else { p.0 };
+            let (lo, hi) = (major(p0).min(major(p1)), major(p0).max(major(p1)));
+            let mut sums: BTreeMap<i32, f32> = BTreeMap::new();
+            for ((x, y), c) in line_aa(p0, p1, &UNCLIPPED) {
+                assert!(c > 0. && c <= 1.);
+                *sums.entry(if steep { y } else { x }).or_insert(0.) += c;
+            }
+            let steps: Vec<i32> = ((lo + 0.5).floor() as i32..(hi + 0.5).ceil() as i32).collect();
+            assert_eq!(sums.keys().cloned().collect::<Vec<_>>(), steps);
+            for (&k, &sum) in &sums {
+                let overlap = (k as f32 + 0.5).min(hi) - (k as f32 - 0.5).max(lo);
+                assert!((sum - overlap).abs() < 1e-5, "step {} of {:?} -> {:?} covers {}", k, p0, p1, sum);
+            }
+        }
+    }
+
+    #[test]
+    fn wu_ignores_endpoint_order() {
+        for &(p0, p1) in &[((0.3, 0.2), (10.7, 4.1)), ((2.6, -3.4), (-1.2, 9.8)), ((4., 4.), (-4., -4.))] {
+            let mut forward = line_aa(p0, p1, &UNCLIPPED);
+            let mut backward = line_aa(p1, p0, &UNCLIPPED);
+            forward.sort_by_key(|&(p, _)| p);
+            backward.sort_by_key(|&(p, _)| p);
+            assert_eq!(forward, backward);
+        }
+    }
+
+    #[test]
+    fn arc_halves_make_ellipse() {
+        let pi = ::std::f32::consts::PI;
+        let mut halves = ellipse_arc((10, 20), 40, 25, 0.3, pi, &UNCLIPPED);
+        halves.extend(ellipse_arc((10, 20), 40, 25, 0.3, -pi, &UNCLIPPED));
+        halves.sort();
+        halves.dedup();
+        let mut whole = ellipse((10, 20), 40, 25, &UNCLIPPED);
+        whole.sort();
+        whole.dedup();
+        assert_eq!(halves, whole);
+    }
+
+    #[test]
+    fn conic_ellipse_is_closed() {
+        use std::collections::HashSet;
+        for &(angle, w, h, shear) in &[(0.5f32, 60., 8., 0.), (0.17, 160., 4., 0.), (1.2, 90., 1.5, 0.), (2.0, 50., 30., 0.8)] {
+            let c = (50.3, 40.7);
+            let u = (w * angle.cos(), w * angle.sin());
+            let v = (-h * angle.sin() + shear * u.0, h * angle.cos() + shear * u.1);
+            let outline = conic_ellipse(c, u, v, &UNCLIPPED);
+            let set: HashSet<Point> = outline.iter().cloned().collect();
+            assert_eq!(set.len(), outline.len());
+            //Flood the outside 4-connected; it must not leak to the center
+            let mut seen: HashSet<Point> = HashSet::new();
+            let mut stack = vec![(-120, -120)];
+            while let Some(p) = stack.pop() {
+                if p.0 < -120 || p.1 < -120 || p.0 > 220 || p.1 > 200 || set.contains(&p) || !seen.insert(p) {
+                    continue;
+                }
+                stack.extend(vec![(p.0 + 1, p.1), (p.0 - 1, p.1), (p.0, p.1 + 1), (p.0, p.1 - 1)]);
+            }
+            for p in fill_conic_ellipse(c, u, v, &UNCLIPPED) {
+                assert!(!seen.contains(&p));
+            }
+            assert!(!seen.contains(&(50, 41)) || set.contains(&(50, 41)));
+            //And the outline itself is one 8-connected piece
+            let mut reached: HashSet<Point> = HashSet::new();
+            let mut stack = vec![outline[0]];
+            while let Some(p) = stack.pop() {
+                if !set.contains(&p) || !reached.insert(p) {
+                    continue;
+                }
+                for dy in -1..2 {
+                    for dx in -1..2 {
+                        stack.push((p.0 + dx, p.1 + dy));
+                    }
+                }
+            }
+            assert_eq!(reached.len(), set.len());
+        }
+    }
+
+    #[test]
+    fn round_rect_matches_fill() {
+        use std::collections::HashSet;
+        let radii = [(0., 0.), (12., 12.), (30., 9.), (5., 20.)];
+        let outline = round_rect((3, 4), (80, 50), radii, &UNCLIPPED);
+        let set: HashSet<Point> = outline.iter().cloned().collect();
+        assert_eq!(set.len(), outline.len());
+        assert!(set.contains(&(3, 4))); //Sharp corner kept
+        let fill = fill_round_rect((3, 4), (80, 50), radii, &UNCLIPPED);
+        let filled: HashSet<Point> = fill.iter().cloned().collect();
+        assert_eq!(filled.len(), fill.len());
+        //Every outline pixel is on the edge of the fill, and no row of the fill sticks out past it
+        assert!(set.is_subset(&filled));
+        for &(x, y) in &fill {
+            if !set.contains(&(x, y)) {
+                assert!(filled.contains(&(x - 1, y)) && filled.contains(&(x + 1, y)));
+                assert!(filled.contains(&(x, y - 1)) && filled.contains(&(x, y + 1)));
+            }
+        }
+    }
+
+    #[test]
+    fn star_follows_fill_rule() {
+        //Pentagram drawn in one stroke; its center is wound twice
+        let star = vec![(20, 5), (29, 32), (6, 15), (34, 15), (11, 32)];
+        let even_odd = fill_polygon(&star, FillRule::EvenOdd, &UNCLIPPED);
+        let non_zero = fill_polygon(&star, FillRule::NonZero, &UNCLIPPED);
+        assert!(!even_odd.contains(&(20, 20)) && non_zero.contains(&(20, 20)));
+        assert!(even_odd.contains(&(20, 9)) && non_zero.contains(&(20, 9))); //A point of the star is wound once
+        let mut both = even_odd.clone();
+        both.retain(|p| !non_zero.contains(p));
+        assert!(both.is_empty());
+        for pixels in &[even_odd, non_zero] {
+            assert_eq!(sorted(pixels.clone()).windows(2).filter(|w| w[0] == w[1]).count(), 0);
+        }
+    }
+
+    #[test]
+    fn horizontal_edges_fill_half_open() {
+        //Pixel centers inside [left, right) and [top, bottom), so a 4 by 3 rectangle is 12 pixels
+        let pixels = fill_polygon(&[(0, 0), (4, 0), (4, 3), (0, 3)], FillRule::EvenOdd, &UNCLIPPED);
+        assert_eq!(sorted(pixels), sorted((0..3).flat_map(|y| (0..4).map(move |x| (x, y))).collect()));
+        //A flat spike along a row adds nothing
+        let pixels = fill_polygon(&[(0, 0), (4, 0), (9, 0), (4, 0), (4, 3), (0, 3)], FillRule::NonZero, &UNCLIPPED);
+        assert_eq!(pixels.len(), 12);
+    }
+
+    #[test]
+    fn shared_edges_fill_once() {
+        let halves = [
+            vec![(0, 0), (5, 0), (5, 4), (0, 4)], vec![(5, 0), (9, 0), (9, 4), (5, 4)], //Straight seam
+            vec![(0, 10), (9, 10), (0, 17)], vec![(9, 10), (9, 17), (0, 17)], //Diagonal seam
+        ];
+        let mut pixels: Vec<Point> = halves.iter().flat_map(|h| fill_polygon(h, FillRule::NonZero, &UNCLIPPED)).collect();
+        pixels.sort();
+        let count = pixels.len();
+        pixels.dedup();
+        assert_eq!(count, pixels.len());
+        let expected: Vec<Point> = (0..4).chain(10..17).flat_map(|y| (0..9).map(move |x| (x, y))).collect();
+        assert_eq!(pixels, sorted(expected));
+    }
+
+    #[test]
+    fn triangles_share_edges_once() {
+        //A square cut along its diagonal, and a fan around an inner point, each tile without gaps or overlap
+        let square = [(1., 1.), (11., 1.), (11., 11.), (1., 11.)];
+        let fans = vec![
+            vec![[square[0], square[1], square[2]], [square[0], square[3], square[2]]],
+            vec![[square[0], square[1], (4.5, 6.5)], [square[1], square[2], (4.5, 6.5)], [square[2], square[3], (4.5, 6.5)], [square[3], square[0], (4.5, 6.5)]],
+        ];
+        for fan in fans {
+            let mut pixels: Vec<Point> = fan.iter().flat_map(|&t| triangle(t, &UNCLIPPED)).map(|(p, _)| p).collect();
+            pixels.sort();
+            let count = pixels.len();
+            pixels.dedup();
+            assert_eq!(count, pixels.len());
+            assert_eq!(pixels, sorted((1..11).flat_map(|y| (1..11).map(move |x| (x, y))).collect()));
+        }
+    }
+
+    #[test]
+    fn triangle_weights_interpolate() {
+        let v = [(0., 0.), (40., 0.), (0., 40.)];
+        for ((x, y), w) in triangle(v, &UNCLIPPED) {
+            assert!((w[0] + w[1] + w[2] - 1.).abs() < 1e-4);
+            let p = (w[1] * 40., w[2] * 40.);
+            assert!((p.0 - (x as f32 + 0.5)).abs() < 1e-3 && (p.1 - (y as f32 + 0.5)).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn clipped_line_is_exact() {
+        //Jumping in partway must land on the same pixels the full walk would have
+        let clip = ((3, -4), (17, 9));
+        let ends = [(-30, 2), (40, 7), (5, -50), (12, 60), (-9, -13), (25, 25), (8, 1)];
+        for &p0 in &ends {
+            for &p1 in &ends {
+                let expected: Vec<Point> = line(p0, p1, &UNCLIPPED).into_iter().filter(|&p| clip_contains(&clip, p)).collect();
+                assert_eq!(line(p0, p1, &clip), expected, "{:?} -> {:?}", p0, p1);
+            }
+        }
+    }
+
+    #[test]
+    fn far_lines_dont_overflow() {
+        let clip = ((0, 0), (9, 9));
+        let (min, max) = (i32::MIN, i32::MAX);
+        assert_eq!(line((min, 5), (max, 5), &clip), (0..10).map(|x| (x, 5)).collect::<Vec<_>>());
+        assert_eq!(line((min, min), (max, max), &clip).len(), 10);
+        assert_eq!(line((min, min), (max, min), &clip), vec![]);
+        //Subpixel ends beyond i32 are cut before they are rounded
+        assert_eq!(polyline(&[(-1e12, 5.), (1e12, 5.)], false, &clip), (0..10).map(|x| (x, 5)).collect::<Vec<_>>());
+        assert_eq!(line_aa((5., -1e12), (5., 1e12), &clip).len(), 10);
+    }
+
+    #[test]
+    fn clipped_shapes_match_filtered() {
+        //Cutting a shape to clip while rasterizing keeps exactly the pixels the whole shape has in clip
+        let clip = ((5, -3), (30, 12));
+        let inside = |points: Vec<Point>| sorted(points.into_iter().filter(|&p| clip_contains(&clip, p)).collect());
+        let radii = [(12., 8.), (5., 5.), (0., 0.), (20., 10.)];
+        let (c, u, v) = ((10.3, 4.7), (25., 9.), (-6., 14.));
+        let star = [(20., -20.), (32., 18.), (-4., -6.), (44., -6.), (8., 18.)];
+        let cases: Vec<(Vec<Point>, Vec<Point>)> = vec![
+            (fill_rect((-10, -10), (20, 40), &clip), fill_rect((-10, -10), (20, 40), &UNCLIPPED)),
+            (fill_polygon_f(&star, FillRule::NonZero, &clip), fill_polygon_f(&star, FillRule::NonZero, &UNCLIPPED)),
+            (fill_ellipse((12, 2), 30, 14, &clip), fill_ellipse((12, 2), 30, 14, &UNCLIPPED)),
+            (ellipse((12, 2), 20, 10, &clip), ellipse((12, 2), 20, 10, &UNCLIPPED)),
+            (ellipse_arc((12, 2), 20, 10, 0.5, 2., &clip), ellipse_arc((12, 2), 20, 10, 0.5, 2., &UNCLIPPED)),
+            (fill_conic_ellipse(c, u, v, &clip), fill_conic_ellipse(c, u, v, &UNCLIPPED)),
+            (conic_ellipse(c, u, v, &clip), conic_ellipse(c, u, v, &UNCLIPPED)),
+            (fill_round_rect((-20, -10), (25, 10), radii, &clip), fill_round_rect((-20, -10), (25, 10), radii, &UNCLIPPED)),
+            (round_rect((-20, -10), (25, 10), radii, &clip), round_rect((-20, -10), (25, 10), radii, &UNCLIPPED)),
+            (
+                triangle([(0., -10.), (40., 5.), (8., 30.)], &clip).into_iter().map(|(p, _)| p).collect(),
+                triangle([(0., -10.), (40., 5.), (8., 30.)], &UNCLIPPED).into_iter().map(|(p, _)| p).collect(),
+            ),
+        ];
+        for (i, (clipped, whole)) in cases.into_iter().enumerate() {
+            assert!(!clipped.is_empty(), "case {}", i);
+            assert_eq!(sorted(clipped), inside(whole), "case {}", i);
+        }
+        //Far larger than clip, so only clip's rows and spans are walked
+        assert_eq!(fill_polygon_f(&[(-1e9, -1e9), (1e9, -1e9), (0., 1e9)], FillRule::NonZero, &clip).len(), 26 * 16);
+        assert_eq!(fill_conic_ellipse((0., 0.), (1e8, 0.), (0., 1e8), &clip).len(), 26 * 16);
+    }
+
+    #[test]
+    fn clip_polyline_keeps_lengths() {
+        let points = [(-100., 5.), (5., 5.), (5., 200.), (50., 200.)];
+        let pieces = clip_polyline(&points, false, 1., &((0, 0), (9, 9)));
+        assert_eq!(pieces, vec![(vec![(-1., 5.), (5., 5.), (5., 11.)], false, 99.)]);
+        //A closed outline cut away from its first vertex stays in one piece through it
+        let square = [(0., 0.), (8., 0.), (8., 100.), (0., 100.)];
+        let pieces = clip_polyline(&square, true, 0., &((-5, -5), (20, 20)));
+        assert_eq!(pieces, vec![(vec![(0., 21.), (0., 0.), (8., 0.), (8., 21.)], false, 195.)]);
+    }
+
+    #[test]
+    fn rotate_full_turn() {
+        let original = vec![(0, 0), (10, 0), (0, 10), (-35, 72), (640, -480)];
+        let mut points = original.clone();
+        points.rotate(Angle::degrees(360.));
+        assert_eq!(points, original);
+        points.rotate_about(Angle::degrees(360.), (100, -100));
+        assert_eq!(points, original);
+    }
+
+    #[test]
+    fn rotate_is_rigid() {
+        let mut points = vec![(100, 0)];
+        points.rotate(Angle::degrees(90.));
+        assert_eq!(points, vec![(0, 100)]);
+        points.rotate_about(Angle::degrees(-90.), (0, 50));
+        assert_eq!(points, vec![(50, 50)]);
+    }
+}